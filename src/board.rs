@@ -1,91 +1,207 @@
 use crate::{space::{Piece, Coord}, ScrambledBoard};
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum GridError {
-    RowIndexOutOfBounds { idx_found: usize, board_size: usize },
-    ColIndexOutOfBounds { idx_found: usize, board_size: usize },
+    RowIndexOutOfBounds { idx_found: usize, rows: usize },
+    ColIndexOutOfBounds { idx_found: usize, cols: usize },
     SpaceOccupied { row: usize, col: usize },
+    SpaceBlocked { row: usize, col: usize },
+    // a piece the rules don't let the player to move place
+    PieceNotAllowed { piece: Piece },
 }
 
 impl Display for GridError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            GridError::RowIndexOutOfBounds {
-                idx_found,
-                board_size,
+            GridError::RowIndexOutOfBounds { idx_found, rows } => {
+                write!(f, "Found row index {}, but board has {} rows", idx_found, rows)
+            }
+            GridError::ColIndexOutOfBounds { idx_found, cols } => {
+                write!(f, "Found col index {}, but board has {} cols", idx_found, cols)
+            }
+            GridError::SpaceOccupied {
+                row: row_idx,
+                col: col_idx,
             } => {
                 write!(
                     f,
-                    "Found row index {}, but board is of size {}",
-                    idx_found, board_size
+                    "Space at row {}, col {} already occupied",
+                    row_idx, col_idx
                 )
             }
-            GridError::ColIndexOutOfBounds {
-                idx_found,
-                board_size,
+            GridError::SpaceBlocked { row, col } => {
+                write!(f, "Space at row {}, col {} is blocked", row, col)
+            }
+            GridError::PieceNotAllowed { piece } => {
+                write!(f, "Player to move can't place {}", piece)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseBoardError {
+    // no rows, or rows with no spaces
+    Empty,
+    // every row has to be as long as the first
+    RowLength { row: usize, len_found: usize, cols: usize },
+    InvalidPiece { row: usize, col: usize, found: char },
+}
+
+impl Display for ParseBoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseBoardError::Empty => write!(f, "Found no spaces"),
+            ParseBoardError::RowLength {
+                row,
+                len_found,
+                cols,
             } => {
                 write!(
                     f,
-                    "Found col index {}, but board is of size {}",
-                    idx_found, board_size
+                    "Found {} spaces in row {}, but the first row has {}",
+                    len_found,
+                    row + 1,
+                    cols
                 )
             }
-            GridError::SpaceOccupied {
-                row: row_idx,
-                col: col_idx,
-            } => {
+            ParseBoardError::InvalidPiece { row, col, found } => {
                 write!(
                     f,
-                    "Space at row {}, col {} already occupied",
-                    row_idx, col_idx
+                    "Found '{}' at row {}, col {}, expected X, O, # or .",
+                    found,
+                    row + 1,
+                    col + 1
                 )
             }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+// "3" for a square board of size 3, or "3x4" for 3 rows of 4 spaces, as written in records, the
+// command line and strategy file names
+pub fn parse_dimensions(s: &str) -> Option<(usize, usize)> {
+    let s = s.trim();
+    let (rows, cols) = s.split_once(['x', 'X']).unwrap_or((s, s));
+    match (rows.trim().parse(), cols.trim().parse()) {
+        (Ok(rows), Ok(cols)) if rows > 0 && cols > 0 => Some((rows, cols)),
+        _ => None,
+    }
+}
+
+pub fn format_dimensions(rows: usize, cols: usize) -> String {
+    if rows == cols {
+        rows.to_string()
+    } else {
+        format!("{}x{}", rows, cols)
+    }
+}
+
+// Boards are written one row at a time from the top, rows separated by '/', with '.' for an
+// empty space and '#' for a blocked one, e.g. "X.O/..X/O.." for
+//
+//     X · O
+//     · · X
+//     O · ·
+//
+// Boards may have any number of rows and columns, not only the same number of each.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "SavedBoard")]
 pub struct Board {
-    pub size: usize,
+    pub rows: usize,
+    pub cols: usize,
     grid: Vec<Piece>,
 }
 
+// Strategies saved before boards could be rectangular have a size instead of rows and cols
+#[derive(Deserialize)]
+struct SavedBoard {
+    size: Option<usize>,
+    rows: Option<usize>,
+    cols: Option<usize>,
+    grid: Vec<Piece>,
+}
+
+impl TryFrom<SavedBoard> for Board {
+    type Error = String;
+
+    fn try_from(saved: SavedBoard) -> Result<Self, Self::Error> {
+        let (rows, cols) = match (saved.rows, saved.cols, saved.size) {
+            (Some(rows), Some(cols), _) => (rows, cols),
+            (None, None, Some(size)) => (size, size),
+            _ => return Err("board needs either rows and cols or a size".to_string()),
+        };
+        if saved.grid.len() != rows * cols {
+            return Err(format!(
+                "board has {} spaces, but should have {} by {}",
+                saved.grid.len(),
+                rows,
+                cols
+            ));
+        }
+        Ok(Self {
+            rows,
+            cols,
+            grid: saved.grid,
+        })
+    }
+}
+
 impl Board {
-    pub fn new(size: usize) -> Self {
-        let grid = vec![Piece::Empty; size*size];
-        Self { size, grid }
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let grid = vec![Piece::Empty; rows*cols];
+        Self { rows, cols, grid }
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
     }
 
     pub fn piece_at(&self, row: usize, col: usize) -> Result<Piece, GridError> {
-        if row >= self.size {
+        if row >= self.rows {
             return Err(GridError::RowIndexOutOfBounds {
                 idx_found: row,
-                board_size: self.size,
+                rows: self.rows,
             });
         }
-        if col >= self.size {
+        if col >= self.cols {
             return Err(GridError::ColIndexOutOfBounds {
                 idx_found: col,
-                board_size: self.size,
+                cols: self.cols,
             });
         }
-        Ok(self.grid[row*self.size + col])
+        Ok(self.grid[row*self.cols + col])
     }
 
     pub fn place(&mut self, p: Piece, row: usize, col: usize) -> Result<(), GridError> {
-        if self.piece_at(row, col)? != Piece::Empty {
-            return Err(GridError::SpaceOccupied { row, col });
+        match self.piece_at(row, col)? {
+            Piece::Empty => (),
+            Piece::Blocked => return Err(GridError::SpaceBlocked { row, col }),
+            _ => return Err(GridError::SpaceOccupied { row, col }),
         }
-        self.grid[row*self.size + col] = p;
+        self.grid[row*self.cols + col] = p;
         Ok(())
     }
 
+    // empties the space at row, col, returning whatever was there
+    pub fn remove(&mut self, row: usize, col: usize) -> Result<Piece, GridError> {
+        let p = self.piece_at(row, col)?;
+        self.grid[row*self.cols + col] = Piece::Empty;
+        Ok(p)
+    }
+
     pub fn flat(&self) -> std::slice::Iter<'_, Piece>{
         self.grid.iter()
     }
 
+    pub fn count(&self, p: Piece) -> usize {
+        self.grid.iter().filter(|&&q| q == p).count()
+    }
+
+    // no empty spaces left, though some may be blocked rather than taken
     pub fn is_full(&self) -> bool {
         self.grid.iter().all(|&p| p != Piece::Empty)
     }
@@ -103,49 +219,88 @@ impl Board {
     }
 
     pub fn has_win(&self, piece: Piece) -> bool {
-        let remaining_rows: Vec<usize> = (0..self.size).collect();
-        let remaining_cols: Vec<usize> = (0..self.size).collect();
-        self.has_win_recursive(piece, &remaining_rows, &remaining_cols)
+        self.transversal(piece).is_some()
     }
 
-    fn has_win_recursive(
-        &self,
-        piece: Piece,
-        remaining_rows: &[usize],
-        remaining_cols: &[usize],
-    ) -> bool {
-        if remaining_rows.is_empty() {
-            return true;
+    // one space of piece's in every row, or in every column if there are fewer columns, with no
+    // two in the same row or column, if there is such a set. Blocked spaces are nobody's, so a row
+    // or column that is all blocked rules out a transversal for both players.
+    pub fn transversal(&self, piece: Piece) -> Option<Vec<Coord>> {
+        self.partial_transversal(piece, self.rows.min(self.cols))
+    }
+
+    // Like transversal, but only needing pieces spaces. Builds a maximum matching of rows to
+    // columns through piece's spaces one augmenting path at a time, stopping once it is big enough.
+    pub fn partial_transversal(&self, piece: Piece, pieces: usize) -> Option<Vec<Coord>> {
+        if pieces > self.rows.min(self.cols) {
+            return None;
         }
-        let row = remaining_rows[0];
-        let remaining_rows = &remaining_rows[1..];
 
-        for (i, &col) in remaining_cols.iter().enumerate() {
-            if self.piece_at(row, col).unwrap() == piece {
-                let mut remaining_cols = remaining_cols.to_owned();
-                remaining_cols.remove(i);
-                if self.has_win_recursive(piece, remaining_rows, &remaining_cols) {
-                    return true;
-                }
+        let mut row_matched_to: Vec<Option<usize>> = vec![None; self.cols];
+        let mut matched = 0;
+        for row in 0..self.rows {
+            if matched == pieces {
+                break;
             }
+            let mut visited = vec![false; self.cols];
+            if self.augment(piece, row, &mut visited, &mut row_matched_to) {
+                matched += 1;
+            }
+        }
+
+        if matched < pieces {
+            return None;
         }
+        let found = row_matched_to
+            .iter()
+            .enumerate()
+            .filter_map(|(col, row)| row.map(|row| Coord { row, col }))
+            .collect();
+        Some(found)
+    }
 
+    // tries to match row to a column of piece's not yet visited, moving rows already matched to
+    // other columns if that frees one up
+    fn augment(
+        &self,
+        piece: Piece,
+        row: usize,
+        visited: &mut [bool],
+        row_matched_to: &mut [Option<usize>],
+    ) -> bool {
+        for col in 0..self.cols {
+            if visited[col] || self.piece_at(row, col).unwrap() != piece {
+                continue;
+            }
+            visited[col] = true;
+            let col_free = match row_matched_to[col] {
+                None => true,
+                Some(other_row) => self.augment(piece, other_row, visited, row_matched_to),
+            };
+            if col_free {
+                row_matched_to[col] = Some(row);
+                return true;
+            }
+        }
         false
     }
 
     pub fn pretty(&self) -> String {
-        let mut display_string = " ".to_string();
+        // labels are padded so that columns stay aligned past row 9 and column Z
+        let row_width = self.rows.to_string().len();
+        let col_width = Coord::col_label(self.cols.saturating_sub(1)).len();
+        let mut display_string = " ".repeat(row_width);
 
-        for col in 0..self.size {
-            display_string += &format!("  {}", (b'A' + col as u8) as char);
+        for col in 0..self.cols {
+            display_string += &format!("  {:<1$}", Coord::col_label(col), col_width);
         }
 
-        for row in 0..self.size {
-            display_string += &format!("\n{}", row + 1);
+        for row in 0..self.rows {
+            display_string += &format!("\n{:>1$}", row + 1, row_width);
 
-            for col in 0..self.size {
-                let this_piece = &self.grid[row*self.size + col];
-                display_string += &format!("  {}", this_piece);
+            for col in 0..self.cols {
+                let this_piece = &self.grid[row*self.cols + col];
+                display_string += &format!("  {}{}", this_piece, " ".repeat(col_width - 1));
             }
         }
 
@@ -155,10 +310,10 @@ impl Board {
 
 impl From<ScrambledBoard> for Board {
     fn from(scrambled: ScrambledBoard) -> Self {
-        let mut b = Self::new(scrambled.size);
+        let mut b = Self::new(scrambled.rows, scrambled.cols);
         
-        for row in 0..b.size {
-            for col in 0..b.size {
+        for row in 0..b.rows {
+            for col in 0..b.cols {
                 b.place(scrambled.piece_at(Coord { row, col }).unwrap(), row, col)
                     .unwrap();
             }
@@ -170,10 +325,10 @@ impl From<ScrambledBoard> for Board {
 
 impl From<&ScrambledBoard> for Board {
     fn from(scrambled: &ScrambledBoard) -> Self {
-        let mut b = Self::new(scrambled.size);
+        let mut b = Self::new(scrambled.rows, scrambled.cols);
         
-        for row in 0..b.size {
-            for col in 0..b.size {
+        for row in 0..b.rows {
+            for col in 0..b.cols {
                 b.place(scrambled.piece_at(Coord { row, col }).unwrap(), row, col)
                     .unwrap();
             }
@@ -185,16 +340,63 @@ impl From<&ScrambledBoard> for Board {
 
 impl Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut display_string = "".to_string();
+        let rows: Vec<String> = self
+            .grid
+            .chunks(self.cols)
+            .map(|row| {
+                row.iter()
+                    .map(|p| match p {
+                        Piece::X => 'X',
+                        Piece::O => 'O',
+                        Piece::Empty => '.',
+                        Piece::Blocked => '#',
+                    })
+                    .collect()
+            })
+            .collect();
+        write!(f, "{}", rows.join("/"))
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseBoardError::Empty);
+        }
+
+        let rows: Vec<&str> = s.split('/').map(|row| row.trim()).collect();
+        let cols = rows[0].chars().count();
+        if cols == 0 {
+            // every other row has to match the first, so none could have any spaces either
+            return Err(ParseBoardError::Empty);
+        }
+        let mut b = Self::new(rows.len(), cols);
+
+        for (row, row_str) in rows.iter().enumerate() {
+            let len_found = row_str.chars().count();
+            if len_found != cols {
+                return Err(ParseBoardError::RowLength {
+                    row,
+                    len_found,
+                    cols,
+                });
+            }
 
-        for row in 0..self.size {
-            for col in 0..self.size {
-                let this_piece = &self.grid[row*self.size + col];
-                display_string += &format!("{}  ", this_piece);
+            for (col, c) in row_str.chars().enumerate() {
+                let piece = match c {
+                    'X' | 'x' => Piece::X,
+                    'O' | 'o' => Piece::O,
+                    '.' => Piece::Empty,
+                    '#' => Piece::Blocked,
+                    found => return Err(ParseBoardError::InvalidPiece { row, col, found }),
+                };
+                b.grid[row * cols + col] = piece;
             }
-            display_string += "\n";
         }
 
-        write!(f, "{}  ", display_string)
+        Ok(b)
     }
 }
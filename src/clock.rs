@@ -0,0 +1,73 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeControl {
+    // one allowance for the whole game
+    SuddenDeath(Duration),
+    // an allowance for the whole game, topped up after every move
+    Increment { base: Duration, increment: Duration },
+    // the same allowance for every move, with nothing carried over
+    PerMove(Duration),
+}
+
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeControl::SuddenDeath(total) => write!(f, "{}s for the game", total.as_secs()),
+            TimeControl::Increment { base, increment } => {
+                write!(f, "{}s + {}s per move", base.as_secs(), increment.as_secs())
+            }
+            TimeControl::PerMove(per_move) => write!(f, "{}s per move", per_move.as_secs()),
+        }
+    }
+}
+
+// One player's time left under a time control
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: Duration,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let remaining = match control {
+            TimeControl::SuddenDeath(total) => total,
+            TimeControl::Increment { base, .. } => base,
+            TimeControl::PerMove(per_move) => per_move,
+        };
+        Self { control, remaining }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    // Takes time spent thinking off the clock. Returns false if it ran out.
+    pub fn charge(&mut self, elapsed: Duration) -> bool {
+        match self.remaining.checked_sub(elapsed) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                true
+            }
+            None => {
+                self.remaining = Duration::ZERO;
+                false
+            }
+        }
+    }
+
+    // adds any time owed for a move just made
+    pub fn finish_move(&mut self) {
+        match self.control {
+            TimeControl::SuddenDeath(_) => (),
+            TimeControl::Increment { increment, .. } => self.remaining += increment,
+            TimeControl::PerMove(per_move) => self.remaining = per_move,
+        }
+    }
+}
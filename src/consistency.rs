@@ -0,0 +1,106 @@
+use rand::Rng;
+use std::fmt::Display;
+
+use crate::players::{AiGroundUp, AiLazy, AiParallel, AiSerial, MoveValue};
+use crate::positions::{canonical_positions, piece_to_play, PositionGenerator};
+use crate::rules::Rules;
+use crate::space::Piece;
+use crate::Board;
+
+#[derive(Debug, Clone)]
+pub struct Disagreement {
+    pub board: Board,
+    pub piece_to_play: Piece,
+    pub evaluations: Vec<(&'static str, MoveValue)>,
+}
+
+impl Display for Disagreement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} to play on {}\n{}\n",
+            self.piece_to_play,
+            self.board,
+            self.board.pretty()
+        )?;
+        for (solver, evaluation) in &self.evaluations {
+            write!(f, "\n  {}: {:?}", solver, evaluation)?;
+        }
+        Ok(())
+    }
+}
+
+// Compares the evaluations of every solver on the same positions. Only the outcome is compared,
+// since AiLazy and AiParallel don't look for the quickest win.
+pub struct ConsistencyChecker {
+    rows: usize,
+    cols: usize,
+    rules: Rules,
+    serial: AiSerial,
+    lazy: AiLazy,
+    parallel: AiParallel,
+    ground_up: AiGroundUp,
+}
+
+impl ConsistencyChecker {
+    pub fn new(rows: usize, cols: usize, rules: Rules) -> Self {
+        Self {
+            rows,
+            cols,
+            rules,
+            serial: AiSerial::new(rows, cols, Piece::X, usize::MAX, rules),
+            lazy: AiLazy::new(rows, cols, Piece::X, true, rules),
+            parallel: AiParallel::new(rows, cols, Piece::X, rules),
+            ground_up: AiGroundUp::new(rows, cols, Piece::X, rules),
+        }
+    }
+
+    pub fn check(&mut self, b: &Board) -> Option<Disagreement> {
+        assert_eq!((b.rows, b.cols), (self.rows, self.cols));
+        let piece_to_play = piece_to_play(b, self.rules);
+
+        let evaluations = vec![
+            ("AiSerial", self.serial.evaluate(piece_to_play, b)),
+            ("AiLazy", self.lazy.evaluate(piece_to_play, b)),
+            ("AiParallel", self.parallel.evaluate(piece_to_play, b)),
+            ("AiGroundUp", self.ground_up.evaluate(piece_to_play, b)),
+        ];
+
+        let reference = &evaluations[0].1;
+        if evaluations.iter().any(|(_, v)| v.conflicts_with(reference)) {
+            Some(Disagreement {
+                board: b.clone(),
+                piece_to_play,
+                evaluations,
+            })
+        } else {
+            None
+        }
+    }
+
+    // checks one board from each standardized class of unfinished positions with at most max_moves pieces
+    pub fn check_all(&mut self, max_moves: usize) -> Vec<Disagreement> {
+        let mut disagreements = Vec::new();
+        for moves in 0..=max_moves {
+            for b in canonical_positions(self.rows, self.cols, moves, self.rules) {
+                disagreements.extend(self.check(&b));
+            }
+        }
+        disagreements
+    }
+
+    pub fn check_random<R: Rng>(&mut self, samples: usize, rng: &mut R) -> Vec<Disagreement> {
+        let mut disagreements = Vec::new();
+
+        for _ in 0..samples {
+            let moves = rng.gen_range(0..self.rows * self.cols);
+            let mut generator =
+                PositionGenerator::with_rng(self.rows, self.cols, self.rules, &mut *rng);
+            if let Some(b) = generator.random_position(moves) {
+                disagreements.extend(self.check(&b));
+            }
+        }
+
+        disagreements
+    }
+}
@@ -1,45 +1,677 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Display;
+use std::time::Instant;
+
 use crate::{
-    players::Player,
-    space::Piece,
+    board::GridError,
+    clock::{Clock, TimeControl},
+    players::{Action, Player, Request},
+    positions::piece_to_play,
+    record::GameRecord,
+    rules::Rules,
+    space::{Coord, Piece},
     Board,
 };
 
-#[derive(Clone, Copy)]
-pub enum GameState {
+// whose move it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
     P1,
     P2,
-    Tie,
 }
 
-pub fn play_game(p1: &mut Box<dyn Player>, p2: &mut Box<dyn Player>, board_size: usize) -> GameState {
-    assert_ne!(p1.piece(), Piece::Empty);
-    assert_ne!(p2.piece(), Piece::Empty);
-    assert_ne!(p1.piece(), p2.piece());
+impl Turn {
+    pub fn other(&self) -> Self {
+        match self {
+            Turn::P1 => Turn::P2,
+            Turn::P2 => Turn::P1,
+        }
+    }
+}
 
-    let mut game_board = Board::new(board_size);
-    println!("\n{}\n\n", game_board.pretty());
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Win { piece: Piece, reason: WinReason },
+    Draw(DrawReason),
+}
 
-    for turn in [GameState::P1, GameState::P2].iter().cycle() {
-        if game_board.is_full() {
-            return GameState::Tie;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WinReason {
+    // the spaces that met the goal and ended the game, which are the loser's in misère
+    Goal(Vec<Coord>),
+    // the board filled up without the Maker meeting the goal in a Maker-Breaker game, which is the
+    // Maker's win in misère
+    GoalNeverMet,
+    Resignation,
+    Timeout,
+    // the loser proposed an illegal move and forfeited
+    IllegalMove(IllegalMove),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawReason {
+    BoardFull,
+    Agreement,
+}
+
+impl Outcome {
+    // None for a draw
+    pub fn winner(&self) -> Option<Piece> {
+        match self {
+            Outcome::Win { piece, .. } => Some(*piece),
+            Outcome::Draw(_) => None,
         }
+    }
+}
 
-        let current_player = match turn {
-            GameState::P1 => &mut *p1,
-            GameState::P2 => &mut *p2,
-            _ => {
-                panic!("turn was neither P1 nor P2, which should never happen");
+impl Display for WinReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WinReason::Goal(coords) => {
+                let coords: Vec<String> = coords.iter().map(|c| c.to_string()).collect();
+                write!(f, "with {}", coords.join(" "))
             }
+            WinReason::GoalNeverMet => write!(f, "with the goal never met"),
+            WinReason::Resignation => write!(f, "by resignation"),
+            WinReason::Timeout => write!(f, "on time"),
+            WinReason::IllegalMove(illegal) => write!(f, "by forfeit ({})", illegal),
+        }
+    }
+}
+
+impl Display for DrawReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DrawReason::BoardFull => write!(f, "the board is full"),
+            DrawReason::Agreement => write!(f, "by agreement"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameEvent {
+    // the piece is the one placed, which is the mover's own unless the rules let them place either
+    Moved { piece: Piece, coord: Coord },
+    Undone { piece: Piece, coord: Coord },
+    Redone { piece: Piece, coord: Coord },
+    DrawOffered(Piece),
+    DrawDeclined(Piece),
+    // the piece's player swapped sides under the pie rule
+    Swapped(Piece),
+    // the piece's player won the coin flip for the next move under random-turn rules
+    CoinFlipped(Piece),
+    SaveRequested,
+    Finished(Outcome),
+}
+
+// a move the player to move proposed that can't be made on the board
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalMove {
+    pub piece: Piece,
+    pub coord: Coord,
+    pub error: GridError,
+}
+
+impl Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Illegal move {} by {}: {}", self.coord, self.piece, self.error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    // counts that can't arise with X moving first, so the side to move has to be given
+    PieceCounts { x_count: usize, o_count: usize },
+    // the game would already be over
+    AlreadyWon(Piece),
+    IllegalMove { coord: Coord, error: GridError },
+}
+
+impl Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionError::PieceCounts { x_count, o_count } => {
+                write!(
+                    f,
+                    "Can't tell who is to move with {} X's and {} O's",
+                    x_count, o_count
+                )
+            }
+            PositionError::AlreadyWon(piece) => {
+                write!(f, "{} has already met the goal", piece)
+            }
+            PositionError::IllegalMove { coord, error } => {
+                write!(f, "Illegal move {}: {}", coord, error)
+            }
+        }
+    }
+}
+
+pub struct Game {
+    rules: Rules,
+    start: Board,
+    p1_starts: bool,
+    board: Board,
+    p1: Box<dyn Player>,
+    p2: Box<dyn Player>,
+    history: Vec<(Coord, Piece)>,
+    undone: Vec<(Coord, Piece)>,
+    outcome: Option<Outcome>,
+    // whether the player able to swap sides under the pie rule did, None until they've decided
+    swapped: Option<bool>,
+    // Under random-turn rules, the piece the coin picked to move next. None under any others, where
+    // the turn follows from the number of pieces placed.
+    random_mover: Option<Piece>,
+    coin: StdRng,
+    // indexed by Turn, None for a player without a clock
+    clocks: [Option<Clock>; 2],
+}
+
+impl Game {
+    pub fn new(
+        p1: Box<dyn Player>,
+        p2: Box<dyn Player>,
+        rows: usize,
+        cols: usize,
+        rules: Rules,
+    ) -> Self {
+        assert_ne!(p1.piece(), Piece::Empty);
+        assert_ne!(p2.piece(), Piece::Empty);
+        assert_ne!(p1.piece(), p2.piece());
+
+        let mut game = Self {
+            rules,
+            start: Board::new(rows, cols),
+            p1_starts: p1.piece() == Piece::X,
+            board: Board::new(rows, cols),
+            p1,
+            p2,
+            history: Vec::new(),
+            undone: Vec::new(),
+            outcome: None,
+            swapped: None,
+            random_mover: None,
+            coin: StdRng::from_entropy(),
+            clocks: [None, None],
+        };
+        game.flip_coin();
+        game
+    }
+
+    // Starts from an arbitrary unfinished position. If to_move is None, it is worked out from
+    // the pieces on the board.
+    pub fn from_position(
+        p1: Box<dyn Player>,
+        p2: Box<dyn Player>,
+        start: Board,
+        to_move: Option<Piece>,
+        rules: Rules,
+    ) -> Result<Self, PositionError> {
+        let side = side_to_move(&start, to_move, rules)?;
+        let mut game = Self::new(p1, p2, start.rows, start.cols, rules);
+        // under random-turn rules the coin already picked who moves first, unless to_move says
+        let to_move = match (game.random_mover, to_move) {
+            (Some(flipped), None) => flipped,
+            _ => side,
         };
+        if game.random_mover.is_some() {
+            game.random_mover = Some(to_move);
+        }
+        game.p1_starts = game.p1.piece() == to_move;
+        game.board = start.clone();
+        game.start = start;
+        if game.board.is_full() {
+            game.outcome = Some(Outcome::Draw(DrawReason::BoardFull));
+        }
+        Ok(game)
+    }
 
-        current_player.make_move(&mut game_board);
+    // resumes a recorded game from where the record leaves off
+    pub fn from_record(
+        p1: Box<dyn Player>,
+        p2: Box<dyn Player>,
+        record: &GameRecord,
+    ) -> Result<Self, PositionError> {
+        let mut game = Self::from_position(
+            p1,
+            p2,
+            record.start.clone(),
+            Some(record.first),
+            record.rules,
+        )?;
+        for (i, &(coord, piece)) in record.moves.iter().enumerate() {
+            if record.swapped && record.swap_point() == Some(i) {
+                game.swap();
+            }
+            if game.random_mover.is_some() {
+                // the coin picked whoever made the move
+                game.random_mover = Some(record.piece_moving(i));
+            }
+            game.play_piece(coord, piece)
+                .map_err(|e| PositionError::IllegalMove { coord, error: e.error })?;
+        }
+        if record.swapped && record.swap_point() == Some(record.moves.len()) {
+            game.swap();
+        }
+        Ok(game)
+    }
+
+    // Seeds the coin flips of a random-turn game, flipping again for the first move if no move has
+    // been made yet
+    pub fn set_seed(&mut self, seed: u64) {
+        self.coin = StdRng::seed_from_u64(seed);
+        if self.history.is_empty() {
+            self.flip_coin();
+        }
+    }
+
+    // Under random-turn rules, picks who moves next, who moves first if no move has been made yet
+    fn flip_coin(&mut self) -> Vec<GameEvent> {
+        if !self.rules.random_turn || self.outcome.is_some() {
+            return vec![];
+        }
+        let piece = if self.coin.gen() { Piece::X } else { Piece::O };
+        self.random_mover = Some(piece);
+        if self.history.is_empty() {
+            self.p1_starts = self.p1.piece() == piece;
+        }
+        vec![GameEvent::CoinFlipped(piece)]
+    }
+
+    // Gives the player taking turn a clock, replacing any clock they had
+    pub fn set_time_control(&mut self, turn: Turn, control: TimeControl) {
+        self.clocks[turn as usize] = Some(Clock::new(control));
+    }
+
+    pub fn clock(&self, turn: Turn) -> Option<&Clock> {
+        self.clocks[turn as usize].as_ref()
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
 
-        println!("\n{}\n\n", game_board.pretty());
+    // the position the game started from
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    // the piece that made the first move from the start position
+    pub fn first_piece(&self) -> Piece {
+        if self.p1_starts {
+            self.p1.piece()
+        } else {
+            self.p2.piece()
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn p1(&self) -> &dyn Player {
+        &*self.p1
+    }
+
+    pub fn p2(&self) -> &dyn Player {
+        &*self.p2
+    }
+
+    pub fn player(&self, turn: Turn) -> &dyn Player {
+        match turn {
+            Turn::P1 => &*self.p1,
+            Turn::P2 => &*self.p2,
+        }
+    }
+
+    // the player playing piece
+    pub fn player_with(&self, piece: Piece) -> &dyn Player {
+        if self.p1.piece() == piece {
+            &*self.p1
+        } else {
+            &*self.p2
+        }
+    }
+
+    // has both players save what they've worked out, e.g. once the game is over, returning the
+    // first failure after both have tried
+    pub fn save_strategies(&mut self) -> std::io::Result<()> {
+        let saved_p1 = self.p1.save_strategy();
+        let saved_p2 = self.p2.save_strategy();
+        saved_p1.and(saved_p2)
+    }
+
+    pub fn current_player(&self) -> &dyn Player {
+        self.player(self.turn())
+    }
 
-        if game_board.has_win(current_player.piece()) {
-            return *turn;
+    pub fn turn(&self) -> Turn {
+        let piece = self
+            .random_mover
+            .unwrap_or_else(|| self.piece_placing(self.placed()));
+        if piece == self.p1.piece() {
+            Turn::P1
+        } else {
+            Turn::P2
         }
     }
-    panic!("Loop ended, which should never happen")
+
+    // pieces placed since the first move, counting every move made since the start position
+    fn placed(&self) -> usize {
+        self.rules.placed(&self.start) + self.history.len()
+    }
+
+    // the piece to place the next piece once `placed` pieces are on the board
+    fn piece_placing(&self, placed: usize) -> Piece {
+        let mut first = self.rules.first_player(&self.start, self.first_piece());
+        if self.swapped == Some(true) {
+            // the pieces placed so far went to the other player, along with first player's place
+            first = first.inverse();
+        }
+        self.rules.piece_placing(placed, first)
+    }
+
+    // whether the current player may swap sides under the pie rule instead of moving
+    pub fn can_swap(&self) -> bool {
+        self.outcome.is_none() && self.swapped.is_none() && self.rules.can_swap(&self.board)
+    }
+
+    // whether a player swapped sides under the pie rule
+    pub fn swapped(&self) -> bool {
+        self.swapped == Some(true)
+    }
+
+    // the piece that made move i since the start position
+    fn mover_of(&self, i: usize) -> Piece {
+        match self.random_mover {
+            Some(_) => self.history[i].1,
+            None => self.piece_placing(self.rules.placed(&self.start) + i),
+        }
+    }
+
+    // how many moves ago the current player last placed a piece, if they have since the start
+    fn moves_since_own_move(&self) -> Option<usize> {
+        let piece = self.current_player().piece();
+        let len = self.history.len();
+        (1..=len).find(|&n| self.mover_of(len - n) == piece)
+    }
+
+    // how many of the moves taken back it takes to reach the current player's turn again, if enough were
+    fn moves_until_own_turn(&self) -> Option<usize> {
+        let piece = self.current_player().piece();
+        let len = self.undone.len();
+        (1..=len).find(|&n| match self.random_mover {
+            // the turn after redoing n is that of whoever made the next move taken back, if any
+            Some(_) => n == len || self.undone[len - n - 1].1 == piece,
+            None => self.piece_placing(self.placed() + n) == piece,
+        })
+    }
+
+    // every move made since the start position, in order, with the piece placed
+    pub fn history(&self) -> &[(Coord, Piece)] {
+        &self.history
+    }
+
+    // None while the game is still in progress
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    // Asks the current player for their move and makes it, or carries out their request instead.
+    // A player proposing an illegal move forfeits, and one taking too long loses on time.
+    // Returns nothing once the game is over.
+    pub fn step(&mut self) -> Vec<GameEvent> {
+        if self.outcome.is_some() {
+            return vec![];
+        }
+
+        if self.can_swap() {
+            let board = &self.board;
+            let accepted = match self.turn() {
+                Turn::P1 => self.p1.accept_swap(board),
+                Turn::P2 => self.p2.accept_swap(board),
+            };
+            if accepted {
+                return self.swap();
+            }
+            self.swapped = Some(false);
+        }
+
+        let turn = self.turn();
+        let (current, opponent) = match turn {
+            Turn::P1 => (&mut self.p1, &mut self.p2),
+            Turn::P2 => (&mut self.p2, &mut self.p1),
+        };
+        let started = Instant::now();
+        let deadline = self.clocks[turn as usize]
+            .as_ref()
+            .map(|clock| started + clock.remaining());
+        let action = current.propose_move(&self.board, deadline);
+
+        if let Some(clock) = &mut self.clocks[turn as usize] {
+            if !clock.charge(started.elapsed()) {
+                return self.time_out();
+            }
+        }
+
+        match action {
+            Action::Place(coord, piece) => match self.play_piece(coord, piece) {
+                Ok(events) => {
+                    if let Some(clock) = &mut self.clocks[turn as usize] {
+                        clock.finish_move();
+                    }
+                    events
+                }
+                Err(illegal) => {
+                    let piece = illegal.piece.inverse();
+                    self.finish(Outcome::Win {
+                        piece,
+                        reason: WinReason::IllegalMove(illegal),
+                    })
+                }
+            },
+            Action::Resign => self.resign(),
+            Action::OfferDraw => {
+                let piece = current.piece();
+                let mut events = vec![GameEvent::DrawOffered(piece)];
+                if opponent.accept_draw(&self.board) {
+                    events.extend(self.finish(Outcome::Draw(DrawReason::Agreement)));
+                } else {
+                    events.push(GameEvent::DrawDeclined(piece.inverse()));
+                }
+                events
+            }
+            // a player's undo takes back their own last move, along with the replies to it
+            Action::Request(Request::Undo) => match self.moves_since_own_move() {
+                Some(n) => (0..n).filter_map(|_| self.undo()).collect(),
+                None => vec![],
+            },
+            Action::Request(Request::Redo) => match self.moves_until_own_turn() {
+                Some(n) => (0..n).flat_map(|_| self.redo()).collect(),
+                None => vec![],
+            },
+            Action::Request(Request::Save) => vec![GameEvent::SaveRequested],
+        }
+    }
+
+    // Places the current player's piece at coord, if the game isn't over and the space is free
+    pub fn play(&mut self, coord: Coord) -> Result<Vec<GameEvent>, IllegalMove> {
+        self.play_piece(coord, self.current_player().piece())
+    }
+
+    // Places piece at coord for the current player, if the rules also let them place that piece
+    pub fn play_piece(&mut self, coord: Coord, piece: Piece) -> Result<Vec<GameEvent>, IllegalMove> {
+        let mover = self.current_player().piece();
+        let illegal = |error| IllegalMove { piece: mover, coord, error };
+        if self.outcome.is_some() {
+            // nothing can be placed once the game is over, so treat every space as taken
+            return Err(illegal(GridError::SpaceOccupied {
+                row: coord.row,
+                col: coord.col,
+            }));
+        }
+        if !self.rules.pieces_for(mover).contains(&piece) {
+            return Err(illegal(GridError::PieceNotAllowed { piece }));
+        }
+
+        self.board.place(piece, coord.row, coord.col).map_err(illegal)?;
+        self.undone.clear();
+        let mut events = vec![GameEvent::Moved { piece, coord }];
+        events.extend(self.record_move(coord, piece));
+        events.extend(self.flip_coin());
+        Ok(events)
+    }
+
+    // Under the pie rule, the current player takes over every piece on the board and the other
+    // player moves next. Does nothing unless the current player may swap.
+    pub fn swap(&mut self) -> Vec<GameEvent> {
+        if !self.can_swap() {
+            return vec![];
+        }
+        let piece = self.current_player().piece();
+        self.board.invert();
+        self.swapped = Some(true);
+        self.undone.clear();
+        vec![GameEvent::Swapped(piece)]
+    }
+
+    // The current player concedes, and the other player wins
+    pub fn resign(&mut self) -> Vec<GameEvent> {
+        let piece = self.current_player().piece().inverse();
+        self.finish(Outcome::Win {
+            piece,
+            reason: WinReason::Resignation,
+        })
+    }
+
+    // The current player ran out of time, and the other player wins. Called by step when a
+    // player's clock runs out, but can also be called for a clock kept outside the game.
+    pub fn time_out(&mut self) -> Vec<GameEvent> {
+        let piece = self.current_player().piece().inverse();
+        self.finish(Outcome::Win {
+            piece,
+            reason: WinReason::Timeout,
+        })
+    }
+
+    // ends the game, unless it is already over
+    fn finish(&mut self, outcome: Outcome) -> Vec<GameEvent> {
+        if self.outcome.is_some() {
+            return vec![];
+        }
+        self.outcome = Some(outcome.clone());
+        vec![GameEvent::Finished(outcome)]
+    }
+
+    // Takes back the last move, if any
+    pub fn undo(&mut self) -> Option<GameEvent> {
+        let (coord, piece) = self.history.pop()?;
+        if self.rules.placed(&self.board) == self.rules.bias.first {
+            // taking back the move before a swap takes back the swap too
+            if self.swapped == Some(true) {
+                self.board.invert();
+            }
+            self.swapped = None;
+        }
+        self.board.remove(coord.row, coord.col).unwrap();
+        self.undone.push((coord, piece));
+        if self.random_mover.is_some() {
+            // whoever made the move gets to make it again
+            self.random_mover = Some(piece);
+        }
+        self.outcome = None;
+        Some(GameEvent::Undone { piece, coord })
+    }
+
+    // Replays the last move taken back, if any, along with any event ending the game
+    pub fn redo(&mut self) -> Vec<GameEvent> {
+        if self.outcome.is_some() {
+            return vec![];
+        }
+        let Some((coord, piece)) = self.undone.pop() else {
+            return vec![];
+        };
+
+        self.board.place(piece, coord.row, coord.col).unwrap();
+        let mut events = vec![GameEvent::Redone { piece, coord }];
+        events.extend(self.record_move(coord, piece));
+        match self.undone.last() {
+            // whoever made the next move taken back moves next, as they did before
+            Some(&(_, next)) if self.random_mover.is_some() => self.random_mover = Some(next),
+            _ => events.extend(self.flip_coin()),
+        }
+        events
+    }
+
+    // Adds a move already placed on the board to the history, and ends the game if it won. Only
+    // the piece placed can have just met the goal.
+    fn record_move(&mut self, coord: Coord, placed: Piece) -> Vec<GameEvent> {
+        let piece = self.current_player().piece();
+        self.history.push((coord, placed));
+
+        if let Some(winner) = self.rules.winner(&self.board, piece) {
+            let spaces = self.rules.winning_spaces(&self.board, placed).unwrap();
+            self.finish(Outcome::Win {
+                piece: winner,
+                reason: WinReason::Goal(spaces),
+            })
+        } else if self.board.is_full() {
+            let to_move = self.rules.next_to_move(&self.board, piece);
+            match self.rules.full_board_winner(&self.board, to_move) {
+                Some(winner) => self.finish(Outcome::Win {
+                    piece: winner,
+                    reason: WinReason::GoalNeverMet,
+                }),
+                None => self.finish(Outcome::Draw(DrawReason::BoardFull)),
+            }
+        } else {
+            vec![]
+        }
+    }
+
+    // Steps until the game is over, passing every event to on_event along the way
+    pub fn play_to_end(&mut self, mut on_event: impl FnMut(&Game, &GameEvent)) -> Outcome {
+        loop {
+            for event in self.step() {
+                on_event(self, &event);
+            }
+            if let Some(outcome) = &self.outcome {
+                return outcome.clone();
+            }
+        }
+    }
+}
+
+// Checks that a game under rules can start from b, and works out who is to move there if not
+// given, assuming X moved first
+pub fn side_to_move(b: &Board, to_move: Option<Piece>, rules: Rules) -> Result<Piece, PositionError> {
+    let side = check_counts(b, to_move, rules)?;
+    // a Breaker's line doesn't end the game
+    let breaker = rules.maker(b, side).map(|maker| maker.inverse());
+    for piece in [Piece::X, Piece::O] {
+        if breaker != Some(piece) && rules.has_win(b, piece) {
+            return Err(PositionError::AlreadyWon(piece));
+        }
+    }
+    Ok(side)
 }
 
+// who is to move on b if not given, once the number of each piece checks out
+fn check_counts(b: &Board, to_move: Option<Piece>, rules: Rules) -> Result<Piece, PositionError> {
+    if let Some(piece) = to_move {
+        return Ok(piece);
+    }
+    if rules.order_chaos.is_some() || rules.random_turn {
+        // either player may have placed any of the pieces
+        return Ok(piece_to_play(b, rules));
+    }
+
+    // any of the pieces could be part of the handicap
+    let (x_count, o_count) = (b.count(Piece::X), b.count(Piece::O));
+    let x_placed = rules.bias.placed_by_first(rules.placed(b));
+    if x_count < x_placed || x_count > x_placed + rules.handicap {
+        return Err(PositionError::PieceCounts { x_count, o_count });
+    }
+    Ok(piece_to_play(b, rules))
+}
@@ -1,10 +1,18 @@
+pub mod clock;
+pub mod consistency;
 pub mod game;
 pub mod space;
 pub mod players;
+pub mod positions;
+pub mod record;
+pub mod replay;
+pub mod rules;
+pub mod strategy;
 pub mod user_input;
+pub mod win_condition;
 
 mod board;
-pub use board::Board;
+pub use board::{format_dimensions, parse_dimensions, Board, GridError, ParseBoardError};
 
 mod scrambled_board;
 pub use scrambled_board::ScrambledBoard;
@@ -13,32 +21,760 @@ pub use scrambled_board::ScrambledBoard;
 mod tests {
     const SIZE: usize = 4;
     use crate::{
-        board::Board,
-        players::{AiLazy, AiParallel, AiSerial, Player},
-        space::Piece,
+        board::{Board, GridError, ParseBoardError},
+        clock::{Clock, TimeControl},
+        consistency::ConsistencyChecker,
+        game::{DrawReason, Game, GameEvent, Outcome, PositionError, Turn, WinReason},
+        players::{
+            read_strategy, write_strategy, AiGroundUp, AiLazy, AiParallel, AiRandom, AiRandomTurn,
+            AiSerial, MoveAnalysis, MoveValue, Player, WinChances,
+        },
+        positions::{piece_to_play, PositionGenerator},
+        record::{GameRecord, RecordError},
+        replay::Replay,
+        rules::{Bias, MakerBreaker, Rules},
+        win_condition::{CustomGoal, Goal, WinCondition},
+        space::{Coord, Piece},
+        ScrambledBoard,
     };
+    use rand::RngCore;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    // Checks that every solver agrees on every position, and returns the value of the empty board
+    // for X moving first. The solvers share Rules::terminal_value, so agreeing alone can't show the
+    // rules are right; the value can be checked against the game's known result.
+    fn solve_checked(rows: usize, cols: usize, rules: Rules) -> MoveValue {
+        let mut checker = ConsistencyChecker::new(rows, cols, rules);
+        let disagreements = checker.check_all(rows * cols);
+        assert!(disagreements.is_empty(), "{}", disagreements[0]);
+        let mut ai = AiSerial::new(rows, cols, Piece::X, usize::MAX, rules);
+        ai.evaluate(Piece::X, &Board::new(rows, cols))
+    }
+
+    fn random_player(piece: Piece) -> Box<dyn Player> {
+        Box::new(AiRandom::new(piece))
+    }
+
+    // a game between two players who move at random
+    fn random_game(rows: usize, cols: usize, rules: Rules) -> Game {
+        Game::new(random_player(Piece::X), random_player(Piece::O), rows, cols, rules)
+    }
 
     #[test]
     fn analyze_ai_serial() {
         let size = SIZE;
-        let mut ai_x = AiSerial::new(size, Piece::X, 100);
-        let mut b = Board::new(size);
-        ai_x.make_move(&mut b);
+        let mut ai_x = AiSerial::new(size, size, Piece::X, 100, Rules::default());
+        let b = Board::new(size, size);
+        ai_x.propose_move(&b, None);
     }
 
     #[test]
     fn analyze_ai_parallel() {
         let size = SIZE;
-        let mut ai_x = AiParallel::new(size, Piece::X);
-        let mut b = Board::new(size);
-        ai_x.make_move(&mut b);
+        let mut ai_x = AiParallel::new(size, size, Piece::X, Rules::default());
+        let b = Board::new(size, size);
+        ai_x.propose_move(&b, None);
     }
 
     #[test]
     fn analyze_ai_lazy() {
         let size = SIZE;
-        let mut ai_x = AiLazy::new(size, Piece::X, true);
-        let mut b = Board::new(size);
-        ai_x.make_move(&mut b);
+        let mut ai_x = AiLazy::new(size, size, Piece::X, true, Rules::default());
+        let b = Board::new(size, size);
+        ai_x.propose_move(&b, None);
+    }
+
+    #[test]
+    fn analyze_ai_ground_up() {
+        let size = SIZE;
+        let mut ai_x = AiGroundUp::new(size, size, Piece::X, Rules::default());
+        let b = Board::new(size, size);
+        ai_x.propose_move(&b, None);
+    }
+
+    #[test]
+    fn save_strategy_into_new_directory() {
+        let dir = std::env::temp_dir().join(format!("tag-strategies-{}", std::process::id()));
+        let path = dir.join("strategies").join("test.cbor");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let analysis = MoveAnalysis {
+            evaluation: MoveValue::Win(1),
+            move_options: vec![(Coord { row: 0, col: 0 }, Piece::X)],
+            depth_used: 1,
+        };
+        let b = Board::new(2, 2);
+        let known_boards = HashMap::from([(b.clone(), analysis)]);
+        write_strategy(path, &known_boards).unwrap();
+
+        let saved: HashMap<Board, MoveAnalysis> = read_strategy(path).unwrap().unwrap();
+        assert_eq!(saved[&b].move_options, known_boards[&b].move_options);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn board_notation() {
+        let b: Board = "X.O/..x/O..".parse().unwrap();
+        assert_eq!(b.piece_at(1, 2), Ok(Piece::X));
+        assert_eq!(b.to_string(), "X.O/..X/O..");
+        assert_eq!(b.to_string().parse::<Board>().unwrap(), b);
+
+        let scrambled = ScrambledBoard::from(b.clone()).into_standardized();
+        let reparsed: ScrambledBoard = scrambled.to_string().parse().unwrap();
+        assert_eq!(Board::from(reparsed), Board::from(scrambled));
+
+        assert_eq!("".parse::<Board>(), Err(ParseBoardError::Empty));
+        assert_eq!("/".parse::<Board>(), Err(ParseBoardError::Empty));
+        assert_eq!("/X.".parse::<Board>(), Err(ParseBoardError::Empty));
+        assert!(matches!(
+            "X./".parse::<Board>(),
+            Err(ParseBoardError::RowLength { row: 1, len_found: 0, cols: 2 })
+        ));
+        assert!(matches!(
+            "X.O/..X/O.".parse::<Board>(),
+            Err(ParseBoardError::RowLength { row: 2, len_found: 2, .. })
+        ));
+        assert!(matches!(
+            "X.O/.?X/O..".parse::<Board>(),
+            Err(ParseBoardError::InvalidPiece { row: 1, col: 1, found: '?' })
+        ));
+    }
+
+    #[test]
+    fn coord_notation() {
+        assert_eq!("b3".parse(), Ok(Coord { row: 2, col: 1 }));
+        assert_eq!("AA12".parse(), Ok(Coord { row: 11, col: 26 }));
+        assert_eq!("r3c2".parse(), Ok(Coord { row: 2, col: 1 }));
+        assert!("A0".parse::<Coord>().is_err());
+        assert!("3A".parse::<Coord>().is_err());
+        assert!("r3c".parse::<Coord>().is_err());
+
+        for col in [0, 25, 26, 701, 702] {
+            let c = Coord { row: 10, col };
+            assert_eq!(c.to_string().parse(), Ok(c));
+        }
+        assert_eq!(Coord::col_label(27), "AB");
+
+        let pretty = Board::new(10, 10).pretty();
+        let lines: Vec<&str> = pretty.lines().collect();
+        assert!(lines[1].starts_with(" 1  "));
+        assert!(lines[10].starts_with("10  "));
+    }
+
+    #[test]
+    fn rectangular_boards() {
+        let b: Board = "X../..X".parse().unwrap();
+        assert_eq!((b.rows, b.cols), (2, 3));
+        assert_eq!(b.to_string(), "X../..X");
+        assert!(b.has_win(Piece::X), "two rows only need two pieces");
+        let standardized = ScrambledBoard::from(b.clone()).into_standardized();
+        assert_eq!((standardized.rows, standardized.cols), (2, 3));
+
+        // X's second piece can always go in the other row and a new column
+        assert_eq!(solve_checked(2, 3, Rules::default()), MoveValue::Win(3));
+        // only the two columns are long enough for a line, and each player can block one
+        assert_eq!(solve_checked(3, 2, Rules::with_goal(Goal::InARow(3))), MoveValue::Tie(6));
+
+        let record: GameRecord = "Size: 2x3\nResult: X\nMoves: A1 B1 C2".parse().unwrap();
+        assert_eq!((record.rows, record.cols), (2, 3));
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+        let parsed: Result<GameRecord, _> = "Size: 3\nStart: X../...".parse();
+        assert!(matches!(parsed, Err(RecordError::StartSizeMismatch { line: 2, .. })));
+
+        // strategies saved before rectangular boards only have a size
+        #[derive(serde::Serialize)]
+        struct OldBoard {
+            size: usize,
+            grid: Vec<Piece>,
+        }
+        let mut saved = Vec::new();
+        let old = OldBoard { size: 2, grid: vec![Piece::X, Piece::Empty, Piece::Empty, Piece::O] };
+        ciborium::ser::into_writer(&old, &mut saved).unwrap();
+        let loaded: Board = ciborium::de::from_reader(&saved[..]).unwrap();
+        assert_eq!(loaded, "X./.O".parse().unwrap());
+    }
+
+    #[test]
+    fn solvers_agree() {
+        assert_eq!(solve_checked(3, 3, Rules::default()), MoveValue::Tie(9));
+    }
+
+    #[test]
+    fn tic_tac_toe() {
+        let rules = Rules::with_goal(Goal::InARow(3));
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Tie(9));
+
+        // a rotation is equivalent, but swapping rows breaks up the diagonal
+        let b: Board = "X../.X./..O".parse().unwrap();
+        let rotated: Board = "..X/.X./O..".parse().unwrap();
+        let swapped: Board = ".X./X../..O".parse().unwrap();
+        let standardized = |b: &Board| Board::from(rules.standardized(ScrambledBoard::from(b.clone())));
+        assert_eq!(standardized(&b), standardized(&rotated));
+        assert_ne!(standardized(&b), standardized(&swapped));
+
+        let diagonal: Board = "X.O/.XO/..X".parse().unwrap();
+        assert_eq!(
+            Goal::InARow(3).winning_spaces(&diagonal, Piece::X),
+            Some(vec![Coord { row: 0, col: 0 }, Coord { row: 1, col: 1 }, Coord { row: 2, col: 2 }])
+        );
+        assert!(!Goal::InARow(3).has_win(&diagonal, Piece::O));
+        assert!(Goal::InARow(2).has_win(&diagonal, Piece::O));
+
+        let record: GameRecord = "Size: 3\nRules: 3-in-a-row\nResult: X\nMoves: A1 A2 B1 B2 C1".parse().unwrap();
+        assert_eq!(record.rules, rules);
+        assert_eq!(record.result.as_ref().and_then(|o| o.winner()), Some(Piece::X));
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+    }
+
+    #[test]
+    fn partial_transversal() {
+        let rules = Rules::with_goal(Goal::PartialTransversal(2));
+        // X's second piece can always go in a new row and column
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Win(3));
+
+        // matching the top row to its first X would leave nothing for the middle row
+        let b: Board = "XX./X../...".parse().unwrap();
+        assert!(Goal::PartialTransversal(2).has_win(&b, Piece::X));
+        assert!(!Goal::PartialTransversal(3).has_win(&b, Piece::X));
+        let b: Board = "XX./X../..X".parse().unwrap();
+        let spaces = Goal::PartialTransversal(3).winning_spaces(&b, Piece::X).unwrap();
+        assert_eq!(spaces.len(), 3);
+        assert!(spaces.iter().all(|c| b.piece_at(c.row, c.col) == Ok(Piece::X)));
+        assert_eq!(Goal::PartialTransversal(3).has_win(&b, Piece::X), b.has_win(Piece::X));
+
+        let parsed: Rules = "misere 2-partial-transversal".parse().unwrap();
+        assert_eq!(parsed, Rules { goal: Goal::PartialTransversal(2), ..Rules::misere() });
+        assert_eq!(parsed.to_string().parse::<Rules>().unwrap(), parsed);
+    }
+
+    // all four corners, as a goal from outside the crate
+    struct Corners;
+
+    impl WinCondition for Corners {
+        fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>> {
+            let (last_row, last_col) = (b.rows - 1, b.cols - 1);
+            let corners: Vec<Coord> = [(0, 0), (0, last_col), (last_row, 0), (last_row, last_col)]
+                .map(|(row, col)| Coord { row, col })
+                .to_vec();
+            corners.iter().all(|c| b.piece_at(c.row, c.col) == Ok(piece)).then_some(corners)
+        }
+
+        fn standardize(&self, b: &mut ScrambledBoard) {
+            b.standardize_dihedral();
+        }
+
+        fn scramble(&self, b: &Board, _rng: &mut dyn RngCore) -> Board {
+            b.clone()
+        }
+    }
+
+    static CORNERS: CustomGoal = CustomGoal { name: "corners", condition: &Corners };
+
+    #[test]
+    fn custom_goal() {
+        let rules = Rules::with_goal(Goal::Custom(&CORNERS));
+        assert_eq!(rules.to_string(), "corners");
+        assert_eq!(rules.goal.file_suffix(), "-corners");
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Tie(9));
+        assert!(rules.has_win(&"X.X/.O./X.X".parse().unwrap(), Piece::X));
+
+        let mut game = random_game(3, 3, rules);
+        if let Some(winner) = game.play_to_end(|_, _| ()).winner() {
+            assert!(rules.has_win(game.board(), winner));
+        }
+    }
+
+    #[test]
+    fn maker_breaker() {
+        // the Breaker wins either way, by filling the board, whoever moves first
+        let rules = Rules::maker_breaker(MakerBreaker::MakerFirst);
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Lose(9));
+        let rules = Rules::maker_breaker(MakerBreaker::BreakerFirst);
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Win(9));
+        // as in tic-tac-toe, a Maker moving first wins with their fourth piece
+        let rules: Rules = "maker-first 3-in-a-row".parse().unwrap();
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Win(7));
+
+        // the Breaker can always take the other space of whichever diagonal the Maker starts
+        let rules = Rules::maker_breaker(MakerBreaker::MakerFirst);
+        let mut ai = AiSerial::new(2, 2, Piece::X, usize::MAX, rules);
+        assert!(matches!(ai.evaluate(Piece::X, &Board::new(2, 2)), MoveValue::Lose(_)));
+
+        let mut game = random_game(2, 2, Rules::maker_breaker(MakerBreaker::BreakerFirst));
+        assert_eq!(game.rules().maker(game.board(), Piece::X), Some(Piece::O));
+        assert!(game.play_to_end(|_, _| ()).winner().is_some(), "Maker-Breaker has no ties");
+
+        let record: GameRecord = "Size: 2\nRules: maker-first\nResult: O\nMoves: A1 B2 B1 A2"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            record.result,
+            Some(Outcome::Win { piece: Piece::O, reason: WinReason::GoalNeverMet })
+        ));
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+
+        // the Breaker meeting the goal doesn't end the game
+        let record: GameRecord = "Size: 3\nRules: maker-first\nMoves: A2 A1 A3 B2 B1 C3 C1"
+            .parse()
+            .unwrap();
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+        let game = Game::from_record(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            &record,
+        )
+        .unwrap();
+        assert!(game.outcome().is_none());
+        assert!(Game::from_position(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            record.board_after(6),
+            None,
+            record.rules,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn biased_turns() {
+        let rules: Rules = "1:2 3-in-a-row".parse().unwrap();
+        assert_eq!(rules.bias, Bias { first: 1, second: 2 });
+        // O's two pieces a turn win tic-tac-toe by O's second turn
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Lose(6));
+        let rules = Rules {
+            bias: Bias { first: 2, second: 1 },
+            ..Rules::maker_breaker(MakerBreaker::BreakerFirst)
+        };
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Win(9));
+
+        let mut game = random_game(3, 3, Rules::biased(Bias { first: 1, second: 2 }));
+        let mut pieces = Vec::new();
+        game.play_to_end(|_, event| {
+            if let GameEvent::Moved { piece, .. } = event {
+                pieces.push(*piece);
+            }
+        });
+        let expected = [Piece::X, Piece::O, Piece::O].into_iter().cycle();
+        assert!(pieces.iter().copied().eq(expected.take(pieces.len())));
+
+        let record: GameRecord = "Size: 3\nRules: 1:2\nMoves: A1 B1 B2 C3".parse().unwrap();
+        assert_eq!(record.piece_moving(2), Piece::O);
+        assert_eq!(record.piece_moving(3), Piece::X);
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+    }
+
+    #[test]
+    fn blocked_and_handicap() {
+        let b: Board = "X#./.#./.#O".parse().unwrap();
+        assert_eq!(b.to_string().parse::<Board>().unwrap(), b);
+        assert_eq!(b.clone().place(Piece::O, 1, 1), Err(GridError::SpaceBlocked { row: 1, col: 1 }));
+        // the blocked middle column leaves no transversal for anyone
+        let b: Board = "X#X/X#X/X#X".parse().unwrap();
+        assert!(!b.has_win(Piece::X));
+
+        let mut checker = ConsistencyChecker::new(3, 3, Rules::with_goal(Goal::InARow(3)));
+        assert!(checker.check(&".../.#./...".parse().unwrap()).is_none());
+        let rules: Rules = "1-handicap maker-first".parse().unwrap();
+        let mut checker = ConsistencyChecker::new(3, 3, rules);
+        assert!(checker.check(&"O#./.../...".parse().unwrap()).is_none());
+
+        // the handicap piece doesn't make O the first player
+        let start: Board = "O../.#./...".parse().unwrap();
+        assert_eq!(rules.maker(&start, Piece::X), Some(Piece::X));
+        let game = Game::from_position(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            start,
+            None,
+            rules,
+        )
+        .unwrap();
+        assert_eq!(game.current_player().piece(), Piece::X);
+
+        let record: GameRecord = "Size: 3\nRules: 1-handicap\nStart: O../.#./...\nMoves: B1 C1 A2"
+            .parse()
+            .unwrap();
+        assert_eq!(record.piece_moving(1), Piece::O);
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+    }
+
+    #[test]
+    fn pie_rule() {
+        let rules: Rules = "pie 3-in-a-row".parse().unwrap();
+        assert_eq!(rules.to_string().parse::<Rules>().unwrap(), rules);
+        // tic-tac-toe is still a tie, one move deeper for the chance to swap
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Tie(10));
+
+        let mut game = random_game(3, 3, rules);
+        assert!(matches!(game.step()[..], [GameEvent::Moved { piece: Piece::X, .. }]));
+        let board_before_swap = game.board().clone();
+        assert!(game.can_swap());
+        assert!(matches!(game.swap()[..], [GameEvent::Swapped(Piece::O)]));
+        assert!(game.swapped());
+        assert_eq!(game.board(), &board_before_swap.inverse());
+        // O now owns the first move, so X places next
+        assert_eq!(game.current_player().piece(), Piece::X);
+        assert!(!game.can_swap());
+
+        let record: GameRecord = "Size: 3\nRules: pie\nMoves: A1 swap B2".parse().unwrap();
+        assert!(record.swapped);
+        assert_eq!(record.piece_moving(1), Piece::X);
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+        assert!(matches!(
+            "Size: 3\nRules: pie\nMoves: A1 B2 swap".parse::<GameRecord>(),
+            Err(RecordError::InvalidSwap { line: 3 })
+        ));
+
+        // the first move is worth the same whether or not the opponent went on to swap
+        let swapped: GameRecord = "Size: 2x3\nRules: pie\nMoves: A1 swap B1".parse().unwrap();
+        let evaluations = Replay::new(swapped).evaluate_moves();
+        assert!(evaluations.iter().all(|e| e.played <= e.best));
+        let unswapped: GameRecord = "Size: 2x3\nRules: pie\nMoves: A1".parse().unwrap();
+        assert_eq!(evaluations[0], Replay::new(unswapped).evaluate_moves()[0]);
+    }
+
+    #[test]
+    fn order_and_chaos() {
+        let rules: Rules = "order-first 3-in-a-row".parse().unwrap();
+        assert_eq!(rules.to_string().parse::<Rules>().unwrap(), rules);
+        assert!("maker-first order-first".parse::<Rules>().is_err());
+        // Order can always finish a line of one piece or the other with their third move
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Win(5));
+
+        // X moved first and so is Order, who wins even when Chaos completes the line
+        let b: Board = "XO./XO./X.O".parse().unwrap();
+        assert_eq!(rules.winner(&b, Piece::O), Some(Piece::X));
+        let b: Board = "XOX/XOO/OXX".parse().unwrap();
+        assert_eq!(rules.full_board_winner(&b, Piece::O), Some(Piece::O));
+
+        let mut game = random_game(3, 3, rules);
+        let (a1, b2) = (Coord { row: 0, col: 0 }, Coord { row: 1, col: 1 });
+        assert!(game.play_piece(a1, Piece::O).is_ok());
+        assert!(game.play_piece(b2, Piece::O).is_ok());
+        assert_eq!(game.history(), &[(a1, Piece::O), (b2, Piece::O)]);
+        let mut standard = random_game(3, 3, Rules::default());
+        let err = standard.play_piece(a1, Piece::O).unwrap_err();
+        assert_eq!(err.error, GridError::PieceNotAllowed { piece: Piece::O });
+
+        let record = GameRecord::from_game(&game);
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+        assert!(matches!(
+            "Size: 3\nMoves: A1=O".parse::<GameRecord>(),
+            Err(RecordError::IllegalMove { line: 2, error: GridError::PieceNotAllowed { .. }, .. })
+        ));
+
+        // strategies saved before moves gave the piece to place have only the space
+        #[derive(serde::Serialize)]
+        struct OldAnalysis {
+            evaluation: MoveValue,
+            move_options: Vec<Coord>,
+            depth_used: usize,
+        }
+        let mut saved = Vec::new();
+        let old = OldAnalysis { evaluation: MoveValue::Win(1), move_options: vec![a1], depth_used: 1 };
+        ciborium::ser::into_writer(&old, &mut saved).unwrap();
+        let mut loaded: MoveAnalysis = ciborium::de::from_reader(&saved[..]).unwrap();
+        loaded.fill_in_pieces(Piece::O);
+        assert_eq!(loaded.move_options, vec![(a1, Piece::O)]);
+    }
+
+    #[test]
+    fn random_turns() {
+        let rules: Rules = "random-turn 3-in-a-row".parse().unwrap();
+        assert_eq!(rules.to_string().parse::<Rules>().unwrap(), rules);
+        assert!("random-turn 1:2".parse::<Rules>().is_err());
+        assert!("random-turn pie".parse::<Rules>().is_err());
+
+        // neither player starts out ahead, and whoever wins the next flip wins the game
+        let mut solver = AiRandomTurn::new(Piece::X, rules);
+        let chances = solver.win_chances(&Board::new(3, 3));
+        assert_eq!(chances.x, chances.o);
+        assert!(chances.x > 0.0 && chances.tie() > 0.0);
+        let b: Board = "XX./OO./...".parse().unwrap();
+        assert_eq!(solver.win_chances(&b), WinChances { x: 0.5, o: 0.5 });
+
+        // the same seed flips the same coins
+        let spaces = [(0, 0), (0, 1), (1, 0), (1, 1)].map(|(row, col)| Coord { row, col });
+        let play = |seed| {
+            let mut game = random_game(3, 3, Rules::random_turn());
+            game.set_seed(seed);
+            for c in spaces {
+                let piece = game.current_player().piece();
+                assert!(game.play_piece(c, piece).is_ok());
+            }
+            game
+        };
+        let mut game = play(7);
+        assert_eq!(game.history(), play(7).history());
+
+        // taking a move back gives the turn back to whoever made it
+        let (_, last) = game.history()[3];
+        game.undo();
+        assert_eq!(game.current_player().piece(), last);
+        game.redo();
+
+        let record = GameRecord::from_game(&game);
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+        let resumed = Game::from_record(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            &record,
+        )
+        .unwrap();
+        assert_eq!(resumed.history(), game.history());
+    }
+
+    #[test]
+    fn misere() {
+        assert_eq!(solve_checked(3, 3, Rules::misere()), MoveValue::Tie(9));
+        // misere tic-tac-toe is a tie too
+        let rules: Rules = "misere 3-in-a-row".parse().unwrap();
+        assert_eq!(solve_checked(3, 3, rules), MoveValue::Tie(9));
+
+        // X completing a transversal loses, so O wins on X's move
+        let b: Board = "XO./OX./..X".parse().unwrap();
+        assert_eq!(Rules::misere().winner(&b, Piece::X), Some(Piece::O));
+        assert_eq!(Rules::default().winner(&b, Piece::X), Some(Piece::X));
+
+        let record: GameRecord = "Size: 3\nRules: misere\nResult: O\nMoves: A1 B1 B2 A2 C3"
+            .parse()
+            .unwrap();
+        assert_eq!(record.rules, Rules::misere());
+        assert_eq!(record.result.as_ref().and_then(|o| o.winner()), Some(Piece::O));
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+    }
+
+    #[test]
+    fn random_positions_are_legal() {
+        let mut generator = PositionGenerator::seeded(SIZE, SIZE, Rules::default(), 0);
+        for moves in 0..=SIZE * SIZE {
+            let b = generator.random_position(moves).unwrap();
+            let expected_to_play = if moves % 2 == 0 { Piece::X } else { Piece::O };
+            assert_eq!(piece_to_play(&b, Rules::default()), expected_to_play);
+            assert_eq!(b.count(Piece::Empty), SIZE * SIZE - moves);
+            assert!(!b.has_win(Piece::X) && !b.has_win(Piece::O));
+        }
+
+        let seeded = || PositionGenerator::seeded(SIZE, SIZE, Rules::default(), 1);
+        let first = seeded().random_canonical_position(6);
+        let second = seeded().random_canonical_position(6);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn game_plays_to_end() {
+        let mut game = Game::new(
+            Box::new(AiRandom::new(Piece::X)),
+            Box::new(AiLazy::new(3, 3, Piece::O, true, Rules::default())),
+            3,
+            3,
+            Rules::default(),
+        );
+
+        let mut moves = 0;
+        let outcome = game.play_to_end(|_, event| {
+            if let GameEvent::Moved { .. } = event {
+                moves += 1;
+            }
+        });
+
+        assert_eq!(game.history().len(), moves);
+        assert_eq!(game.outcome(), Some(&outcome));
+        match outcome {
+            Outcome::Win {
+                piece,
+                reason: WinReason::Goal(coords),
+            } => {
+                assert_eq!(coords.len(), 3);
+                assert!(coords.iter().all(|c| game.board().piece_at(c.row, c.col) == Ok(piece)));
+            }
+            Outcome::Draw(DrawReason::BoardFull) => assert!(game.board().is_full()),
+            other => panic!("unexpected outcome {:?}", other),
+        }
+        assert!(game.step().is_empty());
+    }
+
+    #[test]
+    fn illegal_moves_and_resigning() {
+        let mut game = random_game(SIZE, SIZE, Rules::default());
+        let a1 = Coord { row: 0, col: 0 };
+        assert!(game.play(a1).is_ok());
+        let err = game.play(a1).unwrap_err();
+        assert_eq!((err.piece, err.coord), (Piece::O, a1));
+        assert!(matches!(err.error, GridError::SpaceOccupied { .. }));
+        let err = game.play(Coord { row: SIZE, col: 0 }).unwrap_err();
+        assert!(matches!(err.error, GridError::RowIndexOutOfBounds { .. }));
+        assert_eq!(game.history(), &[(a1, Piece::X)]);
+
+        assert_eq!(
+            game.resign(),
+            vec![GameEvent::Finished(Outcome::Win {
+                piece: Piece::X,
+                reason: WinReason::Resignation
+            })]
+        );
+        assert_eq!(game.outcome().and_then(|o| o.winner()), Some(Piece::X));
+
+        let record: GameRecord = GameRecord::from_game(&game).to_string().parse().unwrap();
+        assert_eq!(record.result.as_ref(), game.outcome());
+    }
+
+    #[test]
+    fn clocks() {
+        let second = Duration::from_secs(1);
+        let mut clock = Clock::new(TimeControl::Increment {
+            base: second,
+            increment: second,
+        });
+        assert!(clock.charge(second / 2));
+        clock.finish_move();
+        assert_eq!(clock.remaining(), second * 3 / 2);
+        assert!(!clock.charge(second * 2));
+
+        let mut clock = Clock::new(TimeControl::PerMove(second));
+        assert!(clock.charge(second / 2));
+        clock.finish_move();
+        assert_eq!(clock.remaining(), second);
+
+        let mut game = Game::new(
+            Box::new(AiSerial::new(3, 3, Piece::X, usize::MAX, Rules::default())),
+            Box::new(AiRandom::new(Piece::O)),
+            3,
+            3,
+            Rules::default(),
+        );
+        game.set_time_control(Turn::P1, TimeControl::SuddenDeath(second * 60));
+        let outcome = game.play_to_end(|_, _| ());
+        assert!(!matches!(outcome, Outcome::Win { reason: WinReason::Timeout, .. }));
+        assert!(game.clock(Turn::P1).unwrap().remaining() < second * 60);
+        assert!(game.clock(Turn::P2).is_none());
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut game = random_game(SIZE, SIZE, Rules::default());
+        for _ in 0..3 {
+            assert!(matches!(game.step()[..], [GameEvent::Moved { .. }]));
+        }
+        let board_after_three = game.board().clone();
+        let history_after_three = game.history().to_vec();
+
+        assert!(matches!(game.undo(), Some(GameEvent::Undone { piece: Piece::X, .. })));
+        assert!(matches!(game.undo(), Some(GameEvent::Undone { piece: Piece::O, .. })));
+        assert_eq!(game.history(), &history_after_three[..1]);
+        assert_eq!(game.board().count(Piece::Empty), SIZE * SIZE - 1);
+        assert_eq!(game.current_player().piece(), Piece::O);
+
+        assert!(matches!(game.redo()[..], [GameEvent::Redone { piece: Piece::O, .. }]));
+        assert!(matches!(game.redo()[..], [GameEvent::Redone { piece: Piece::X, .. }]));
+        assert_eq!(game.board(), &board_after_three);
+        assert_eq!(game.history(), &history_after_three[..]);
+        assert!(game.redo().is_empty());
+
+        assert!(game.undo().is_some());
+        assert!(matches!(game.step()[..], [GameEvent::Moved { piece: Piece::X, .. }]));
+        assert!(game.redo().is_empty(), "a new move discards moves taken back");
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let mut game = random_game(SIZE, SIZE, Rules::default());
+        game.play_to_end(|_, _| ());
+
+        let mut record = GameRecord::from_game(&game);
+        record.comments.push("a random game".to_string());
+        let parsed: GameRecord = record.to_string().parse().unwrap();
+        assert_eq!(parsed, record);
+        assert_eq!(&parsed.board_after(parsed.moves.len()), game.board());
+
+        let resumed = Game::from_record(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            &parsed,
+        )
+        .unwrap();
+        assert_eq!(resumed.board(), game.board());
+        assert_eq!(resumed.outcome(), game.outcome());
+    }
+
+    #[test]
+    fn record_errors() {
+        let parsed: Result<GameRecord, _> = "Size: 3\nMoves: A1 B2\nMoves: A1".parse();
+        assert!(matches!(parsed, Err(RecordError::IllegalMove { line: 3, .. })));
+
+        let parsed: Result<GameRecord, _> = "# no size\nMoves: A1".parse();
+        assert_eq!(parsed, Err(RecordError::MissingField("Size")));
+
+        let parsed: Result<GameRecord, _> = "Size: 2\nMoves: A1 B1 B2 A2".parse();
+        assert!(matches!(parsed, Err(RecordError::MoveAfterEnd { line: 2, .. })));
+
+        let parsed: Result<GameRecord, _> = "Size: 3\nResult: maybe".parse();
+        assert!(matches!(parsed, Err(RecordError::InvalidResult { line: 2, .. })));
+
+        let parsed: Result<GameRecord, _> = "Size: 3\nResult: X\nMoves: A1".parse();
+        assert_eq!(parsed, Err(RecordError::ResultMismatch { line: 2 }));
+
+        let parsed: GameRecord = "Size: 3\nResult: X\nTermination: illegal A1\nMoves: A1"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            parsed.result,
+            Some(Outcome::Win { piece: Piece::X, reason: WinReason::IllegalMove(_) })
+        ));
+    }
+
+    #[test]
+    fn game_from_position() {
+        let record: GameRecord = "Start: X.O/.X./...\nMoves: C3".parse().unwrap();
+        assert_eq!(record.first, Piece::O);
+        assert_eq!(record.board_after(1).piece_at(2, 2), Ok(Piece::O));
+        assert_eq!(record.to_string().parse::<GameRecord>().unwrap(), record);
+
+        let lopsided: Board = "O.../..../..../....".parse().unwrap();
+        let game = Game::from_position(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            lopsided.clone(),
+            None,
+            Rules::default(),
+        );
+        assert!(matches!(game, Err(PositionError::PieceCounts { x_count: 0, o_count: 1 })));
+
+        let mut game = Game::from_position(
+            random_player(Piece::X),
+            random_player(Piece::O),
+            lopsided,
+            Some(Piece::O),
+            Rules::default(),
+        )
+        .unwrap();
+        assert_eq!(game.current_player().piece(), Piece::O);
+        game.play_to_end(|_, _| ());
+        assert!(game.undo().is_some());
+
+        let parsed: GameRecord = GameRecord::from_game(&game).to_string().parse().unwrap();
+        assert_eq!(parsed.first, Piece::O);
+        assert_eq!(&parsed.board_after(parsed.moves.len()), game.board());
+    }
+
+    #[test]
+    fn replay_steps_and_evaluates() {
+        let record: GameRecord = "Size: 3\nMoves: A1 B1 B2 C1 C3".parse().unwrap();
+        let mut replay = Replay::new(record.clone());
+
+        assert!(!replay.back());
+        assert!(replay.forward());
+        assert_eq!(replay.last_move(), Some((Piece::X, record.moves[0])));
+        replay.to_end();
+        assert!(!replay.forward());
+        assert_eq!(replay.board(), record.board_after(5));
+        assert!(replay.board().has_win(Piece::X));
+
+        let evaluations = replay.evaluate_moves();
+        assert_eq!(evaluations.len(), 5);
+        assert!(evaluations.iter().all(|e| e.played <= e.best));
+        assert_eq!(evaluations[4].played, MoveValue::Win(1));
     }
 }
@@ -1,35 +1,492 @@
+use std::env;
+
+use rand::thread_rng;
+
 #[allow(unused_imports)]
 use tag::{
-    game,
-    user_input,
-    players::{AiLazy, AiParallel, AiSerial, Human, Player},
-    Board, space::Piece,
+    consistency::ConsistencyChecker,
+    positions::{piece_to_play, PositionGenerator},
+    record::GameRecord,
+    rules::Rules,
+    replay::{MoveEvaluation, Replay},
+    game::{Game, GameEvent, Outcome, Turn},
+    user_input::{self, ReplayCommand},
+    players::{AiLazy, AiParallel, AiRandomTurn, AiSerial, Human, Player},
+    strategy::{Solver, Strategy, StrategyFile},
+    format_dimensions, parse_dimensions, Board, space::Piece,
 };
 
 fn main() {
-    play();
+    let args: Vec<String> = env::args().collect();
+
+    match args.get(1).map(|s| s.as_str()) {
+        Some("resume") if args.len() == 3 => resume(&args[2]),
+        Some("start") if args.len() == 3 => start(&args[2], None),
+        Some("start") if args.len() == 4 => match args[3].parse() {
+            Ok(moves) => start(&args[2], Some(moves)),
+            Err(_) => print_usage(),
+        },
+        Some("from") if args.len() == 3 || args.len() == 4 => {
+            let to_move = match args.get(3).map(|s| s.to_uppercase()).as_deref() {
+                None => Some(None),
+                Some("X") => Some(Some(Piece::X)),
+                Some("O") => Some(Some(Piece::O)),
+                Some(_) => None,
+            };
+            match to_move {
+                Some(to_move) => from_position(&args[2], to_move),
+                None => print_usage(),
+            }
+        }
+        Some("replay") if args.len() == 3 => replay(&args[2], false),
+        Some("replay") if args.len() == 4 && args[3] == "evaluate" => replay(&args[2], true),
+        Some("merge") if args.len() >= 4 => merge(&args[2], &args[3..]),
+        Some("convert") if args.len() == 4 => merge(&args[3], &args[2..3]),
+        Some("verify") if args.len() == 3 => verify(&args[2]),
+        Some("check") if args.len() == 5 || args.len() == 6 => {
+            let rules = args.get(5).map_or(Ok(Rules::default()), |s| s.parse());
+            match (parse_dimensions(&args[2]), args[4].parse(), rules) {
+                (Some(size), Ok(n), Ok(rules)) if args[3] == "all" => check(size, n, false, rules),
+                (Some(size), Ok(n), Ok(rules)) if args[3] == "random" => check(size, n, true, rules),
+                _ => print_usage(),
+            }
+        }
+        Some("solve") if args.len() == 3 || args.len() == 4 => {
+            let rules = args.get(3).map_or(Ok(Rules::default()), |s| s.parse());
+            match (parse_dimensions(&args[2]), rules) {
+                (Some(size), Ok(rules)) => solve(size, rules),
+                _ => print_usage(),
+            }
+        }
+        Some("random") if (4..=6).contains(&args.len()) => {
+            // either may be left out, and only the seed is a number
+            let mut optional = &args[4..];
+            let seed = optional.first().and_then(|s| s.parse::<u64>().ok());
+            if seed.is_some() {
+                optional = &optional[1..];
+            }
+            let rules = match optional {
+                [] => Some(Rules::default()),
+                [rules] => rules.parse().ok(),
+                _ => None,
+            };
+            match (parse_dimensions(&args[2]), args[3].parse(), rules) {
+                (Some((rows, cols)), Ok(moves), Some(rules)) => {
+                    let generator = match seed {
+                        Some(seed) => PositionGenerator::seeded(rows, cols, rules, seed),
+                        None => PositionGenerator::new(rows, cols, rules),
+                    };
+                    random_position(generator, moves, rules)
+                }
+                _ => print_usage(),
+            }
+        }
+        Some(_) => print_usage(),
+        None => play(),
+    }
+}
+
+fn print_usage() {
+    println!("Usage:");
+    println!("  tag                                  play a game");
+    println!("  tag resume <record>                  continue a saved game");
+    println!("  tag start <record> [moves]           play a new game from a saved game's position after some moves");
+    println!("  tag from <position> [X|O]            play a new game from a position such as X.O/..X/O..");
+    println!("  tag replay <record> [evaluate]       step through a saved game, optionally with each move's value");
+    println!("  tag merge <output> <input>...        merge strategy files into one");
+    println!("  tag convert <input> <output>         convert a strategy file to another solver's format");
+    println!("  tag verify <input>                   check a strategy file for inconsistent or missing entries");
+    println!("  tag check <size> all <max moves> [rules]   compare every solver on all positions up to max moves");
+    println!("  tag check <size> random <samples> [rules]  compare every solver on random positions");
+    println!("  tag solve <size> [rules]             work out who wins from the empty board");
+    println!("  tag random <size> <moves> [seed] [rules]   print a random unfinished position");
+    println!();
+    println!("Rules are standard if not given, otherwise any of misere, pie, maker-first, breaker-first,");
+    println!("order-first, chaos-first or random-turn, pieces per turn such as 1:2 and a goal such as");
+    println!("3-in-a-row or 3-partial-transversal, e.g. \"maker-first 1:2 3-in-a-row\".");
+    println!("Sizes are a single number for a square board, or rows by columns such as 3x4.");
+    println!("Positions are written with . for an empty space and # for a blocked one.");
+    println!("Random positions grow from an empty board, so they have no blocked spaces or handicap, and");
+    println!("each set of positions the solvers treat as equivalent is as likely as any other.");
+    println!("Strategy file names must follow the solvers' own naming, e.g. strategies/serial-s4-pX-d16.cbor");
 }
 
 #[allow(dead_code)]
 fn play() {
-    let board_size = user_input::get_board_size();
+    let (rows, cols) = user_input::get_board_size();
+    println!();
+
+    let start = user_input::get_start(rows, cols);
     println!();
 
-    let mut p1 = user_input::get_player(Piece::X, board_size);
+    let mut rules = user_input::get_rules(rows, cols);
+    // every piece already on the board is a handicap
+    rules.handicap = start.count(Piece::X) + start.count(Piece::O);
+    println!();
+
+    let p1 = user_input::get_player(Piece::X, rows, cols, rules);
     println!();
     
-    let mut p2 = user_input::get_player(Piece::O, board_size);
+    let p2 = user_input::get_player(Piece::O, rows, cols, rules);
     println!();
 
-    match game::play_game(&mut p1, &mut p2, board_size) {
-        game::GameState::P1 => {
-            println!("{} wins!", p1)
-        },
-        game::GameState::P2 => {
-            println!("{} wins!", p2)
+    // under random-turn rules the coin decides who moves first
+    let to_move = Some(Piece::X).filter(|_| !rules.random_turn);
+    match Game::from_position(p1, p2, start, to_move, rules) {
+        Ok(game) => run(game),
+        Err(e) => println!("Could not start: {}", e),
+    }
+}
+
+fn resume(path: &str) {
+    let record = match GameRecord::load(path) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("Could not read {}: {}", path, e);
+            return;
         }
-        game::GameState::Tie => {
-            println!("It's a tie!")
+    };
+
+    let p1 = user_input::get_player(Piece::X, record.rows, record.cols, record.rules);
+    println!();
+
+    let p2 = user_input::get_player(Piece::O, record.rows, record.cols, record.rules);
+    println!();
+
+    match Game::from_record(p1, p2, &record) {
+        Ok(game) => run(game),
+        Err(e) => println!("Could not resume {}: {}", path, e),
+    }
+}
+
+// starts a new game from the position after the given number of moves, or after all of them
+fn start(path: &str, moves: Option<usize>) {
+    let record = match GameRecord::load(path) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("Could not read {}: {}", path, e);
+            return;
         }
+    };
+    let moves = moves.unwrap_or(record.moves.len()).min(record.moves.len());
+
+    let p1 = user_input::get_player(Piece::X, record.rows, record.cols, record.rules);
+    println!();
+
+    let p2 = user_input::get_player(Piece::O, record.rows, record.cols, record.rules);
+    println!();
+
+    let start = record.board_after(moves);
+    let to_move = Some(record.piece_moving(moves)).filter(|_| !record.rules.random_turn);
+    match Game::from_position(p1, p2, start, to_move, record.rules) {
+        Ok(game) => run(game),
+        Err(e) => println!("Could not start from {}: {}", path, e),
+    }
+}
+
+// starts a new game from a board in notation, with the side to move worked out if not given
+fn from_position(position: &str, to_move: Option<Piece>) {
+    let start: Board = match position.parse() {
+        Ok(b) => b,
+        Err(e) => {
+            println!("Could not read position {}: {}", position, e);
+            return;
+        }
+    };
+
+    let rules = user_input::get_rules(start.rows, start.cols);
+    println!();
+
+    let p1 = user_input::get_player(Piece::X, start.rows, start.cols, rules);
+    println!();
+
+    let p2 = user_input::get_player(Piece::O, start.rows, start.cols, rules);
+    println!();
+
+    match Game::from_position(p1, p2, start, to_move, rules) {
+        Ok(game) => run(game),
+        Err(e) => println!("Could not start from {}: {}", position, e),
+    }
+}
+
+fn run(mut game: Game) {
+    for turn in [Turn::P1, Turn::P2] {
+        if let Some(control) = user_input::get_time_control(game.player(turn).piece()) {
+            game.set_time_control(turn, control);
+        }
+        println!();
+    }
+
+    let maker = game.rules().maker(game.board(), game.current_player().piece());
+    if let Some(maker) = maker {
+        println!(
+            "{} is the Maker and {} the Breaker",
+            game.player_with(maker),
+            game.player_with(maker.inverse())
+        );
+    }
+    let order = game.rules().order(game.board(), game.current_player().piece());
+    if let Some(order) = order {
+        println!(
+            "{} is Order and {} is Chaos, and either may place X or O",
+            game.player_with(order),
+            game.player_with(order.inverse())
+        );
+    }
+    if game.rules().random_turn && game.outcome().is_none() {
+        println!("{} wins the coin flip", game.current_player());
+    }
+
+    println!("\n{}\n\n", game.board().pretty());
+
+    game.play_to_end(|game, event| match event {
+        GameEvent::Moved { .. } | GameEvent::Undone { .. } | GameEvent::Redone { .. } => {
+            println!("\n{}\n", game.board().pretty());
+            for turn in [Turn::P1, Turn::P2] {
+                if let Some(clock) = game.clock(turn) {
+                    println!("{}: {:.1?} left", game.player(turn), clock.remaining());
+                }
+            }
+            println!();
+        }
+        GameEvent::DrawOffered(piece) => {
+            println!("{} offers a draw", game.player_with(*piece))
+        }
+        GameEvent::DrawDeclined(piece) => {
+            println!("{} declines the draw", game.player_with(*piece))
+        }
+        GameEvent::Swapped(piece) => {
+            println!("{} swaps sides", game.player_with(*piece));
+            println!("\n{}\n", game.board().pretty());
+        }
+        GameEvent::CoinFlipped(piece) => {
+            println!("{} wins the coin flip", game.player_with(*piece))
+        }
+        GameEvent::SaveRequested => save(game),
+        GameEvent::Finished(Outcome::Win { piece, reason }) => {
+            println!("{} wins {}!", game.player_with(*piece), reason)
+        }
+        GameEvent::Finished(Outcome::Draw(reason)) => {
+            println!("It's a tie, {}!", reason)
+        }
+    });
+
+    println!();
+    if let Err(e) = game.save_strategies() {
+        println!("Could not save strategy: {}", e);
+    }
+    save(&game);
+}
+
+fn save(game: &Game) {
+    if let Some(path) = user_input::get_save_path() {
+        match GameRecord::from_game(game).save(&path) {
+            Ok(()) => println!("Saved game to {}", path),
+            Err(e) => println!("Could not save {}: {}", path, e),
+        }
+    }
+}
+
+fn replay(path: &str, evaluate: bool) {
+    let mut replay = match GameRecord::load(path) {
+        Ok(r) => Replay::new(r),
+        Err(e) => {
+            println!("Could not read {}: {}", path, e);
+            return;
+        }
+    };
+
+    let evaluations: Vec<MoveEvaluation> = if evaluate && replay.record().rules.random_turn {
+        println!("Moves can't be evaluated under random-turn rules");
+        vec![]
+    } else if evaluate {
+        println!("Evaluating moves...");
+        replay.evaluate_moves()
+    } else {
+        vec![]
+    };
+
+    let record = replay.record();
+    println!("\n{} vs {}", Piece::X.colorize(&record.x_player), Piece::O.colorize(&record.o_player));
+    for comment in &record.comments {
+        println!("# {}", comment);
+    }
+
+    loop {
+        let total = replay.record().moves.len();
+        match replay.last_move() {
+            None => println!("\nStart of game"),
+            Some((mover, (coord, piece))) => {
+                print!("\nMove {} of {}: {} plays ", replay.position(), total, mover);
+                if piece == mover {
+                    print!("{}", coord);
+                } else {
+                    print!("{} at {}", piece, coord);
+                }
+                if let Some(e) = evaluations.get(replay.position() - 1) {
+                    if e.played == e.best {
+                        print!("  ({:?}, best move)", e.played);
+                    } else {
+                        print!("  ({:?}, best was {:?})", e.played, e.best);
+                    }
+                }
+                println!();
+            }
+        }
+        println!("\n{}\n", replay.board().pretty());
+
+        let can_go_back = replay.position() > 0;
+        let can_go_forward = replay.position() < total;
+        match user_input::get_replay_command(can_go_back, can_go_forward) {
+            ReplayCommand::Forward => {
+                replay.forward();
+            }
+            ReplayCommand::Back => {
+                replay.back();
+            }
+            ReplayCommand::Start => replay.to_start(),
+            ReplayCommand::End => replay.to_end(),
+            ReplayCommand::Quit => return,
+        }
+    }
+}
+
+fn merge(output: &str, inputs: &[String]) {
+    let mut merged: Option<Strategy> = None;
+
+    for path in inputs {
+        let strategy = match Strategy::load(path) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("Could not read {}: {}", path, e);
+                return;
+            }
+        };
+        println!("Read {} positions from {}", strategy.known_boards.len(), path);
+
+        let Some(m) = &mut merged else {
+            merged = Some(strategy);
+            continue;
+        };
+        match m.merge(strategy) {
+            Ok(conflicts) => {
+                for c in &conflicts {
+                    println!(
+                        "Conflict: kept {:?} over {:?} for\n{}\n",
+                        c.kept,
+                        c.discarded,
+                        c.board.pretty()
+                    );
+                }
+                println!("{} conflicting positions in {}", conflicts.len(), path);
+            }
+            Err(e) => {
+                println!("Could not merge {}: {}", path, e);
+                return;
+            }
+        }
+    }
+
+    let merged = merged.expect("at least one input is required");
+    match merged.save(output) {
+        Ok(()) => println!("Saved strategy to {}", output),
+        Err(e) => println!("Could not save {}: {}", output, e),
+    }
+}
+
+fn verify(path: &str) {
+    let strategy = match Strategy::load(path) {
+        Ok(s) => s,
+        Err(e) => {
+            println!("Could not read {}: {}", path, e);
+            return;
+        }
+    };
+    let strict = matches!(
+        StrategyFile::from_path(path).unwrap().solver,
+        Solver::Serial { .. } | Solver::GroundUp
+    );
+
+    let inconsistencies = strategy.verify(strict);
+    for i in &inconsistencies {
+        println!("{}\n", i);
+    }
+    println!(
+        "Checked {} positions, found {} problems",
+        strategy.known_boards.len(),
+        inconsistencies.len()
+    );
+}
+
+fn check((rows, cols): (usize, usize), n: usize, random: bool, rules: Rules) {
+    if rules.random_turn {
+        println!("Only one solver handles random-turn rules, so there are none to compare");
+        return;
+    }
+    let mut checker = ConsistencyChecker::new(rows, cols, rules);
+    let disagreements = if random {
+        checker.check_random(n, &mut thread_rng())
+    } else {
+        checker.check_all(n)
+    };
+
+    for d in &disagreements {
+        println!("{}\n", d);
+    }
+    println!("Found {} disagreements", disagreements.len());
+}
+
+// a full-depth solve of the empty board, with X moving first unless a coin flip decides each turn
+fn solve((rows, cols): (usize, usize), rules: Rules) {
+    let b = Board::new(rows, cols);
+    if let Some(maker) = rules.maker(&b, Piece::X) {
+        println!("{} is the Maker", maker);
+    }
+    if let Some(order) = rules.order(&b, Piece::X) {
+        println!("{} is Order", order);
+    }
+    if rules.random_turn {
+        let chances = AiRandomTurn::new(Piece::X, rules).win_chances(&b);
+        println!(
+            "Chances on an empty {} board under {} rules: {} {}, {} {}, tie {}",
+            format_dimensions(rows, cols),
+            rules,
+            Piece::X,
+            chances.x,
+            Piece::O,
+            chances.o,
+            chances.tie()
+        );
+        return;
+    }
+
+    // picks up where any earlier solve of the same board left off, and saves what it finds
+    let mut solver = AiSerial::new(rows, cols, Piece::X, usize::MAX, rules);
+    if let Err(e) = solver.load_strategy() {
+        println!("Could not read saved strategy: {}", e);
+    }
+    solver.start_checkpointing();
+    let evaluation = solver.evaluate(Piece::X, &b);
+    println!(
+        "{} to play on an empty {} board under {} rules: {:?}",
+        Piece::X,
+        format_dimensions(rows, cols),
+        rules,
+        evaluation
+    );
+    if let Err(e) = solver.save_strategy() {
+        println!("Could not save strategy: {}", e);
+    }
+}
+
+fn random_position(mut generator: PositionGenerator, moves: usize, rules: Rules) {
+    if rules.handicap > 0 {
+        // the positions grow from an empty board, with nothing placed or blocked beforehand
+        println!("Random positions start from an empty board, so there is no handicap to give");
+        return;
+    }
+    match generator.random_canonical_position(moves) {
+        Some(b) => println!("{}\n\n{}\n\n{} to play", b, b.pretty(), piece_to_play(&b, rules)),
+        None => println!("Could not find an unfinished position after {} moves", moves),
     }
 }
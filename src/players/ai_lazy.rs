@@ -1,25 +1,53 @@
-use ciborium::{de, ser};
 use rand::{seq::SliceRandom, thread_rng};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::File;
+use std::time::Instant;
 
-use super::available_spaces;
-use super::{MoveValue, Player};
+use super::{available_moves, key_for, piece_from_key, place_for_key};
+use super::{read_inverted_strategy, read_strategy, write_strategy};
+use super::{MoveAnalysis, MoveValue, Player, Action};
+use crate::board::format_dimensions;
+use crate::rules::Rules;
 use crate::space::{Coord, Piece};
+use crate::strategy::StrategyError;
 use crate::Board;
 use crate::ScrambledBoard;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LazyMoveAnalysis {
     pub evaluation: MoveValue,
-    pub move_option: Option<Coord>,
+    // a space and the piece to place there
+    #[serde(deserialize_with = "super::saved_move")]
+    pub move_option: Option<(Coord, Piece)>,
+}
+
+// keeps only the first of the move options, the one AiLazy would have chosen
+impl From<MoveAnalysis> for LazyMoveAnalysis {
+    fn from(analysis: MoveAnalysis) -> Self {
+        Self {
+            evaluation: analysis.evaluation,
+            move_option: analysis.move_options.first().copied(),
+        }
+    }
+}
+
+impl LazyMoveAnalysis {
+    // gives a move read from a strategy saved with the space alone the piece the strategy keys positions by
+    pub(crate) fn fill_in_piece(&mut self, piece: Piece) {
+        if let Some((_, p)) = &mut self.move_option {
+            if *p == Piece::Empty {
+                *p = piece;
+            }
+        }
+    }
 }
 
 pub struct AiLazy {
-    size: usize,
+    rows: usize,
+    cols: usize,
     piece: Piece,
+    rules: Rules,
     known_boards: HashMap<Board, LazyMoveAnalysis>,
     deterministic: bool,
 }
@@ -31,59 +59,69 @@ impl Display for AiLazy {
 }
 
 impl Player for AiLazy {
-    fn make_move(&mut self, game_board: &mut Board) {
-        assert_eq!(game_board.size, self.size);
+    fn propose_move(&mut self, game_board: &Board, _deadline: Option<Instant>) -> Action {
+        assert_eq!((game_board.rows, game_board.cols), (self.rows, self.cols));
 
-        let chosen_move = self.choose_move(self.piece, game_board);
-        game_board
-            .place(self.piece(), chosen_move.row, chosen_move.col)
-            .unwrap();
+        let (coord, piece) = self.choose_move(self.piece, game_board);
+        Action::Place(coord, piece)
     }
 
     fn piece(&self) -> Piece {
         self.piece
     }
+
+    fn description(&self) -> String {
+        "Lazy AI".to_string()
+    }
+
+    fn accept_swap(&mut self, game_board: &Board) -> bool {
+        let evaluation = self.evaluate(self.piece, game_board);
+        evaluation.for_parent() > evaluation
+    }
+
+    fn save_strategy(&mut self) -> std::io::Result<()> {
+        AiLazy::save_strategy(self)
+    }
 }
 
 impl AiLazy {
-    pub fn new(size: usize, piece: Piece, deterministic: bool) -> Self {
+    pub fn new(rows: usize, cols: usize, piece: Piece, deterministic: bool, rules: Rules) -> Self {
         Self {
-            size,
+            rows,
+            cols,
             piece,
+            rules,
             known_boards: HashMap::new(),
             deterministic,
         }
     }
 
-    fn choose_move(&mut self, piece_to_play: Piece, game_board: &Board) -> Coord {
-        let mut scrambled = ScrambledBoard::from(game_board.clone());
-        if piece_to_play != self.piece {
-            scrambled.invert();
-        }
-        scrambled.standardize();
+    fn choose_move(&mut self, piece_to_play: Piece, game_board: &Board) -> (Coord, Piece) {
+        let scrambled = key_for(piece_to_play, self.piece, game_board, &self.rules);
 
         let key = Board::from(&scrambled);
 
         let analysis = self.analyze(&key);
 
-        let mut chosen_move = analysis.move_option.unwrap();
+        let (mut chosen_move, piece) = analysis.move_option.unwrap();
         chosen_move = scrambled.space_at(chosen_move).unwrap().to_coord();
+        let piece = piece_from_key(piece, self.piece, piece_to_play);
 
         match self.deterministic {
-            true => chosen_move,
-            false => self.equivalent_move(chosen_move, &game_board),
+            true => (chosen_move, piece),
+            false => (self.equivalent_move(chosen_move, piece, game_board), piece),
         }
     }
 
-    fn equivalent_move(&self, reference_coord: Coord, b: &Board) -> Coord {
+    fn equivalent_move(&self, reference_coord: Coord, piece: Piece, b: &Board) -> Coord {
         let mut moves: HashMap<(usize, usize), Board> = HashMap::new();
 
-        for row in 0..b.size {
-            for col in 0..b.size {
+        for row in 0..b.rows {
+            for col in 0..b.cols {
                 let mut this_board = b.clone();
-                if this_board.place(self.piece, row, col).is_ok() {
+                if this_board.place(piece, row, col).is_ok() {
                     let standardized =
-                        Board::from(ScrambledBoard::from(this_board).into_standardized());
+                        Board::from(self.rules.standardized(ScrambledBoard::from(this_board)));
                     moves.insert((row, col), standardized);
                 }
             }
@@ -102,25 +140,21 @@ impl AiLazy {
         equivalent.choose(&mut thread_rng()).unwrap().clone()
     }
 
+    pub fn evaluate(&mut self, piece_to_play: Piece, game_board: &Board) -> MoveValue {
+        let scrambled = key_for(piece_to_play, self.piece, game_board, &self.rules);
+        self.analyze(&Board::from(scrambled)).evaluation
+    }
+
     fn analyze(&mut self, b: &Board) -> LazyMoveAnalysis {
         // assumes it is getting an already-standardized board
         if let Some(analysis) = self.known_boards.get(b) {
             return analysis.clone();
         }
 
-        if b.has_win(self.piece.inverse()) {
-            // b already has other player winning
+        if let Some(evaluation) = self.rules.terminal_value(b, self.piece) {
+            // game already over
             let new_analysis = LazyMoveAnalysis {
-                evaluation: MoveValue::Lose(0),
-                move_option: None,
-            };
-            self.known_boards.insert(b.clone(), new_analysis.clone());
-            return new_analysis;
-        }
-
-        if b.is_full() {
-            let new_analysis = LazyMoveAnalysis {
-                evaluation: MoveValue::Tie(0),
+                evaluation,
                 move_option: None,
             };
             self.known_boards.insert(b.clone(), new_analysis.clone());
@@ -128,28 +162,26 @@ impl AiLazy {
         }
 
         // recursive case
-        let mut best_coord = *available_spaces(b).get(0).unwrap();
+        let moves = available_moves(b, self.piece, &self.rules);
+        let mut best_move = moves[0];
         let mut best_evaluation = MoveValue::Lose(0); // lowest possible evaluation to start
-        for c in available_spaces(b) {
-            let mut recursion_board = b.clone();
-            recursion_board.place(self.piece, c.row, c.col).unwrap();
-            recursion_board.invert();
+        for m in moves {
+            let (recursion_board, turn_passed) = place_for_key(b, self.piece, m, &self.rules);
+            let can_swap = self.rules.can_swap(&recursion_board);
             let mut scrambled = ScrambledBoard::from(recursion_board);
-            scrambled.standardize();
+            self.rules.standardize(&mut scrambled);
             let mut lower_analysis = self.analyze(&Board::from(scrambled));
 
-            lower_analysis.evaluation = match lower_analysis.evaluation {
-                MoveValue::Lose(v) => MoveValue::Win(v + 1),
-                MoveValue::Tie(v) => MoveValue::Tie(v + 1),
-                MoveValue::Unknown(v) => MoveValue::Unknown(v + 1),
-                MoveValue::Win(v) => MoveValue::Lose(v + 1),
-            };
+            if can_swap {
+                lower_analysis.evaluation = lower_analysis.evaluation.with_swap();
+            }
+            lower_analysis.evaluation = lower_analysis.evaluation.for_mover(turn_passed);
 
             // short circuit as soon as a win is found. Thus considers every win equally optimal
             if let MoveValue::Win(_) = lower_analysis.evaluation {
                 let new_analysis = LazyMoveAnalysis {
                     evaluation: lower_analysis.evaluation,
-                    move_option: Some(c),
+                    move_option: Some(m),
                 };
 
                 self.known_boards.insert(b.clone(), new_analysis.clone());
@@ -157,14 +189,14 @@ impl AiLazy {
             }
 
             if lower_analysis.evaluation > best_evaluation {
-                best_coord = c;
+                best_move = m;
                 best_evaluation = lower_analysis.evaluation;
             }
         }
 
         let new_analysis = LazyMoveAnalysis {
             evaluation: best_evaluation,
-            move_option: Some(best_coord),
+            move_option: Some(best_move),
         };
         self.known_boards.insert(b.clone(), new_analysis.clone());
 
@@ -183,31 +215,39 @@ impl AiLazy {
             Piece::O => "O",
             _ => "_",
         };
-        format!("strategies/lazy-s{}-p{}-lazy.cbor", self.size, piece_str)
+        format!(
+            "strategies/lazy-s{}-p{}-lazy{}.cbor",
+            format_dimensions(self.rows, self.cols),
+            piece_str,
+            self.rules.file_suffix()
+        )
     }
 
-    pub fn save_strategy(&self) {
-        let buffer = File::create(&self.cbor_path(false)).unwrap(); // TODO: make safe
-        ser::into_writer(&self.known_boards, buffer).unwrap();
+    pub fn save_strategy(&self) -> std::io::Result<()> {
+        write_strategy(&self.cbor_path(false), &self.known_boards)?;
         println!("Saved strategy to {}", self.cbor_path(false));
+        Ok(())
     }
 
-    pub fn load_strategy(&mut self) -> Option<()> {
-        if let Ok(f) = File::open(self.cbor_path(false)) {
-            self.known_boards = de::from_reader(f).unwrap();
+    // reads any saved strategy, returning whether there was one
+    pub fn load_strategy(&mut self) -> Result<bool, StrategyError> {
+        if let Some(known_boards) = read_strategy(&self.cbor_path(false))? {
+            self.known_boards = known_boards;
             println!("Read strategy from {}", self.cbor_path(false));
-            Some(())
-        } else if let Ok(f) = File::open(self.cbor_path(true)) {
-            let known_boards_inverted: HashMap<Board, LazyMoveAnalysis> =
-                de::from_reader(f).unwrap();
-            for b in known_boards_inverted.keys() {
-                let analysis = known_boards_inverted.get(b).unwrap();
-                self.known_boards.insert(b.inverse(), analysis.clone());
-            }
+        } else if let Some(known_boards) =
+            read_inverted_strategy(&self.cbor_path(true), self.piece)?
+        {
+            self.known_boards = known_boards
+                .into_iter()
+                .map(|(b, analysis)| (b, analysis.into()))
+                .collect();
             println!("Read strategy from {}", self.cbor_path(true));
-            Some(())
         } else {
-            None
+            return Ok(false);
+        }
+        for analysis in self.known_boards.values_mut() {
+            analysis.fill_in_piece(self.piece);
         }
+        Ok(true)
     }
 }
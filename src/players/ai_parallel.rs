@@ -1,14 +1,20 @@
-use ciborium::{de, ser};
 use rand::{seq::SliceRandom, thread_rng};
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::File;
+use std::time::Instant;
 
 use rayon::prelude::*;
-use std::sync::{Arc, RwLock};
-
-use super::{available_spaces, MoveAnalysis, MoveValue, Player};
+use std::sync::{Arc, Mutex, RwLock};
+
+use super::checkpoint::{self, Checkpoint};
+use super::{
+    available_moves, key_for, piece_from_key, place_for_key, read_inverted_strategy, read_strategy,
+    write_strategy, Action, MoveAnalysis, MoveValue, Player,
+};
+use crate::board::format_dimensions;
+use crate::rules::Rules;
 use crate::space::{Coord, Piece};
+use crate::strategy::StrategyError;
 use crate::Board;
 use crate::ScrambledBoard;
 
@@ -16,9 +22,12 @@ const MAX_SERIAL_DEPTH: usize = 3; // magic value found experimentally
 const MAX_DEPTH: usize = 100;
 
 pub struct AiParallel {
-    size: usize,
+    rows: usize,
+    cols: usize,
     piece: Piece,
+    rules: Rules,
     known_boards: Arc<RwLock<HashMap<Board, MoveAnalysis>>>,
+    checkpoint: Option<Mutex<Checkpoint>>,
 }
 
 impl Display for AiParallel {
@@ -28,57 +37,68 @@ impl Display for AiParallel {
 }
 
 impl Player for AiParallel {
-    fn make_move(&mut self, game_board: &mut Board) {
-        let chosen_move = self.choose_move(self.piece, game_board);
-        game_board
-            .place(self.piece(), chosen_move.row, chosen_move.col)
-            .unwrap();
+    fn propose_move(&mut self, game_board: &Board, _deadline: Option<Instant>) -> Action {
+        let (coord, piece) = self.choose_move(self.piece, game_board);
+        Action::Place(coord, piece)
     }
 
     fn piece(&self) -> Piece {
         self.piece
     }
+
+    fn description(&self) -> String {
+        "Parallel AI".to_string()
+    }
+
+    fn accept_swap(&mut self, game_board: &Board) -> bool {
+        let evaluation = self.evaluate(self.piece, game_board);
+        evaluation.for_parent() > evaluation
+    }
+
+    fn save_strategy(&mut self) -> std::io::Result<()> {
+        AiParallel::save_strategy(self)
+    }
 }
 
 impl AiParallel {
-    pub fn new(size: usize, piece: Piece) -> Self {
+    pub fn new(rows: usize, cols: usize, piece: Piece, rules: Rules) -> Self {
         Self {
-            size,
+            rows,
+            cols,
             piece,
+            rules,
             known_boards: Arc::new(RwLock::new(HashMap::new())),
+            checkpoint: None,
         }
     }
 
-    fn choose_move(&mut self, piece_to_play: Piece, game_board: &Board) -> Coord {
-        let mut scrambled = ScrambledBoard::from(game_board.clone());
-        if piece_to_play != self.piece {
-            // if asking to play a different piece than known_boards assumes
-            scrambled.invert();
-        }
-        scrambled.standardize();
+    fn choose_move(&mut self, piece_to_play: Piece, game_board: &Board) -> (Coord, Piece) {
+        let scrambled = key_for(piece_to_play, self.piece, game_board, &self.rules);
 
         let key = Board::from(&scrambled);
 
         let analysis = self.analyze(&key, 0, &Vec::new());
 
-        let mut chosen_move_initial = *analysis
+        let (mut chosen_move_initial, piece) = *analysis
             .move_options
             .choose(&mut rand::thread_rng())
             .unwrap();
         chosen_move_initial = scrambled.space_at(chosen_move_initial).unwrap().to_coord();
+        let piece = piece_from_key(piece, self.piece, piece_to_play);
 
-        self.equivalent_move(chosen_move_initial, &game_board)
+        (self.equivalent_move(chosen_move_initial, piece, game_board), piece)
     }
 
-    fn equivalent_move(&self, reference_coord: Coord, b: &Board) -> Coord {
+    // a random space where placing piece leads to the same standardized board as placing it at reference_coord
+    fn equivalent_move(&self, reference_coord: Coord, piece: Piece, b: &Board) -> Coord {
         let mut moves: HashMap<(usize, usize), Board> = HashMap::new();
 
-        for row in 0..b.size {
-            for col in 0..b.size {
+        for row in 0..b.rows {
+            for col in 0..b.cols {
                 let mut this_board = b.clone();
-                if this_board.place(self.piece, row, col).is_ok() {
+                if this_board.place(piece, row, col).is_ok() {
                     let standardized =
-                        Board::from(ScrambledBoard::from(this_board).into_standardized());
+                        Board::from(self.rules.standardized(ScrambledBoard::from(this_board)));
                     moves.insert((row, col), standardized);
                 }
             }
@@ -97,6 +117,11 @@ impl AiParallel {
         equivalent.choose(&mut thread_rng()).unwrap().clone()
     }
 
+    pub fn evaluate(&mut self, piece_to_play: Piece, game_board: &Board) -> MoveValue {
+        let scrambled = key_for(piece_to_play, self.piece, game_board, &self.rules);
+        self.analyze(&Board::from(scrambled), 0, &Vec::new()).evaluation
+    }
+
     fn analyze(
         &self,
         b: &Board,
@@ -109,49 +134,32 @@ impl AiParallel {
             return analysis.clone();
         }
 
-        if b.has_win(self.piece.inverse()) {
-            // b already has other player winning
+        if let Some(evaluation) = self.rules.terminal_value(b, self.piece) {
+            // game already over
             let new_analysis = MoveAnalysis {
-                evaluation: MoveValue::Lose(0),
+                evaluation,
                 move_options: vec![],
                 depth_used: MAX_DEPTH, // max depth because no need to ever reanalyze this position deeper
             };
-            self.known_boards
-                .write()
-                .unwrap()
-                .insert(b.clone(), new_analysis.clone());
-            return new_analysis;
-        }
-
-        if b.is_full() {
-            let new_analysis = MoveAnalysis {
-                evaluation: MoveValue::Tie(0),
-                move_options: vec![],
-                depth_used: MAX_DEPTH, // max depth because no need to ever reanalyze this position deeper
-            };
-            self.known_boards
-                .write()
-                .unwrap()
-                .insert(b.clone(), new_analysis.clone());
+            self.remember(b, &new_analysis);
             return new_analysis;
         }
 
         // recursive case
         let win_found = RwLock::new(false);
-        let mut new_analyses: Vec<(Coord, MoveAnalysis)>;
+        let mut new_analyses: Vec<((Coord, Piece), MoveAnalysis)>;
         new_analyses = if current_depth <= MAX_SERIAL_DEPTH {
             // serial
-            available_spaces(b)
+            available_moves(b, self.piece, &self.rules)
                 .into_iter()
-                .map(|c| {
+                .map(|m| {
                     if *win_found.read().unwrap() || parents.iter().any(|rw| *rw.read().unwrap()) {
                         return None;
                     }
-                    let mut b = b.clone();
-                    b.place(self.piece, c.row, c.col).unwrap();
-                    b.invert();
+                    let (b, turn_passed) = place_for_key(b, self.piece, m, &self.rules);
+                    let can_swap = self.rules.can_swap(&b);
                     let mut scrambled = ScrambledBoard::from(b);
-                    scrambled.standardize();
+                    self.rules.standardize(&mut scrambled);
 
                     let mut parents_inner = parents.clone();
                     parents_inner.push(Arc::new(RwLock::new(false)));
@@ -159,36 +167,33 @@ impl AiParallel {
                     let mut lower_analysis =
                         self.analyze(&Board::from(scrambled), current_depth + 1, &parents_inner);
 
-                    lower_analysis.evaluation = match lower_analysis.evaluation {
-                        MoveValue::Lose(v) => MoveValue::Win(v + 1),
-                        MoveValue::Tie(v) => MoveValue::Tie(v + 1),
-                        MoveValue::Unknown(v) => MoveValue::Unknown(v + 1),
-                        MoveValue::Win(v) => MoveValue::Lose(v + 1),
-                    };
+                    if can_swap {
+                        lower_analysis.evaluation = lower_analysis.evaluation.with_swap();
+                    }
+                    lower_analysis.evaluation = lower_analysis.evaluation.for_mover(turn_passed);
 
                     if let MoveValue::Win(_) = lower_analysis.evaluation {
                         *parents_inner.last().unwrap().write().unwrap() = true;
                         *win_found.write().unwrap() = true;
                     }
 
-                    Some((c, lower_analysis))
+                    Some((m, lower_analysis))
                 })
                 .map_while(|a| a)
                 .collect()
         } else {
             // parallel
-            available_spaces(b)
+            available_moves(b, self.piece, &self.rules)
                 .into_par_iter()
-                .filter_map(|c| {
+                .filter_map(|m| {
                     if *win_found.read().unwrap() || parents.iter().any(|rw| *rw.read().unwrap()) {
                         return None;
                     }
 
-                    let mut b = b.clone();
-                    b.place(self.piece, c.row, c.col).unwrap();
-                    b.invert();
+                    let (b, turn_passed) = place_for_key(b, self.piece, m, &self.rules);
+                    let can_swap = self.rules.can_swap(&b);
                     let mut scrambled = ScrambledBoard::from(b);
-                    scrambled.standardize();
+                    self.rules.standardize(&mut scrambled);
 
                     let mut parents_inner = parents.clone();
                     parents_inner.push(Arc::new(RwLock::new(false)));
@@ -196,19 +201,17 @@ impl AiParallel {
                     let mut lower_analysis =
                         self.analyze(&Board::from(scrambled), current_depth + 1, &parents_inner);
 
-                    lower_analysis.evaluation = match lower_analysis.evaluation {
-                        MoveValue::Lose(v) => MoveValue::Win(v + 1),
-                        MoveValue::Tie(v) => MoveValue::Tie(v + 1),
-                        MoveValue::Unknown(v) => MoveValue::Unknown(v + 1),
-                        MoveValue::Win(v) => MoveValue::Lose(v + 1),
-                    };
+                    if can_swap {
+                        lower_analysis.evaluation = lower_analysis.evaluation.with_swap();
+                    }
+                    lower_analysis.evaluation = lower_analysis.evaluation.for_mover(turn_passed);
 
                     if let MoveValue::Win(_) = lower_analysis.evaluation {
                         *parents_inner.last().unwrap().write().unwrap() = true;
                         *win_found.write().unwrap() = true;
                     }
 
-                    Some((c, lower_analysis))
+                    Some((m, lower_analysis))
                 })
                 .collect()
         }; // parallel
@@ -246,12 +249,19 @@ impl AiParallel {
             depth_used,
         };
 
+        self.remember(b, &new_analysis);
+
+        new_analysis
+    }
+
+    fn remember(&self, b: &Board, analysis: &MoveAnalysis) {
         self.known_boards
             .write()
             .unwrap()
-            .insert(b.clone(), new_analysis.clone());
-
-        new_analysis
+            .insert(b.clone(), analysis.clone());
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint.lock().unwrap().record(b, analysis);
+        }
     }
 
     pub fn cbor_path(&self, inverted: bool) -> String {
@@ -267,35 +277,57 @@ impl AiParallel {
             _ => "_",
         };
         format!(
-            "strategies/parallel-s{}-p{}-lazy.cbor",
-            self.size, piece_str
+            "strategies/parallel-s{}-p{}-lazy{}.cbor",
+            format_dimensions(self.rows, self.cols),
+            piece_str,
+            self.rules.file_suffix()
         )
     }
 
-    pub fn save_strategy(&self) {
-        let buffer = File::create(&self.cbor_path(false)).unwrap(); // TODO: make safe
-        ser::into_writer(&*self.known_boards.read().unwrap(), buffer).unwrap();
+    pub fn checkpoint_path(&self) -> String {
+        self.cbor_path(false).replace(".cbor", ".log")
+    }
+
+    // resumes from any existing checkpoint, then logs every new analysis to it
+    pub fn start_checkpointing(&mut self) {
+        let path = self.checkpoint_path();
+        let mut known_boards = self.known_boards.write().unwrap();
+        if let Some(count) = checkpoint::replay(&path, &mut known_boards) {
+            for analysis in known_boards.values_mut() {
+                analysis.fill_in_pieces(self.piece);
+            }
+            println!("Resumed {} positions from {}", count, path);
+        }
+        drop(known_boards);
+        self.checkpoint = Some(Mutex::new(Checkpoint::open(&path).unwrap())); // TODO: make safe
+    }
+
+    pub fn save_strategy(&self) -> std::io::Result<()> {
+        write_strategy(&self.cbor_path(false), &*self.known_boards.read().unwrap())?;
         println!("Saved strategy to {}", self.cbor_path(false));
+
+        // everything in the checkpoint is now in the saved strategy
+        if let Some(checkpoint) = &self.checkpoint {
+            checkpoint.lock().unwrap().clear();
+        }
+        Ok(())
     }
 
-    pub fn load_strategy(&mut self) -> Option<()> {
-        if let Ok(f) = File::open(self.cbor_path(false)) {
-            *self.known_boards.write().unwrap() = de::from_reader(f).unwrap();
+    // reads any saved strategy, returning whether there was one
+    pub fn load_strategy(&mut self) -> Result<bool, StrategyError> {
+        let mut known_boards = self.known_boards.write().unwrap();
+        if let Some(saved) = read_strategy(&self.cbor_path(false))? {
+            *known_boards = saved;
             println!("Read strategy from {}", self.cbor_path(false));
-            Some(())
-        } else if let Ok(f) = File::open(self.cbor_path(true)) {
-            let known_boards_inverted: HashMap<Board, MoveAnalysis> = de::from_reader(f).unwrap();
-            for b in known_boards_inverted.keys() {
-                let analysis = known_boards_inverted.get(b).unwrap();
-                self.known_boards
-                    .write()
-                    .unwrap()
-                    .insert(b.inverse(), analysis.clone());
-            }
+        } else if let Some(saved) = read_inverted_strategy(&self.cbor_path(true), self.piece)? {
+            *known_boards = saved;
             println!("Read strategy from {}", self.cbor_path(true));
-            Some(())
         } else {
-            None
+            return Ok(false);
+        }
+        for analysis in known_boards.values_mut() {
+            analysis.fill_in_pieces(self.piece);
         }
+        Ok(true)
     }
 }
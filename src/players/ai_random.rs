@@ -1,7 +1,8 @@
 use std::fmt::Display;
+use std::time::Instant;
 use crate::Board;
 use crate::space::Piece;
-use super::{Player, available_spaces};
+use super::{Player, Action, available_spaces};
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 use rand::rngs::ThreadRng;
@@ -18,17 +19,20 @@ impl Display for AiRandom {
 }
 
 impl Player for AiRandom {
-    fn make_move(&mut self, game_board: &mut Board) {
-        let spaces = available_spaces(&game_board);
+    fn propose_move(&mut self, game_board: &Board, _deadline: Option<Instant>) -> Action {
+        let spaces = available_spaces(game_board);
         let chosen_move = spaces.choose(&mut self.rng).unwrap();
-        game_board
-            .place(self.piece(), chosen_move.row, chosen_move.col)
-            .unwrap();
+        // its own piece, which every variant allows
+        Action::Place(*chosen_move, self.piece)
     }
 
     fn piece(&self) -> Piece {
         self.piece
     }
+
+    fn description(&self) -> String {
+        "Random AI".to_string()
+    }
 }
 
 impl AiRandom {
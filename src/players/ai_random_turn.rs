@@ -0,0 +1,144 @@
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::time::Instant;
+
+use super::{available_spaces, Action, Player};
+use crate::rules::Rules;
+use crate::space::{Coord, Piece};
+use crate::Board;
+use crate::ScrambledBoard;
+
+// The chances of each piece winning from a position under random-turn rules, where a coin flip
+// decides who moves each turn and both players play their best. Whatever is left over is the
+// chance of a tie. They are exact: each is a multiple of 2^-n for a board of n spaces, which an
+// f64 holds without rounding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinChances {
+    pub x: f64,
+    pub o: f64,
+}
+
+impl WinChances {
+    fn won_by(piece: Piece) -> Self {
+        match piece {
+            Piece::X => Self { x: 1.0, o: 0.0 },
+            _ => Self { x: 0.0, o: 1.0 },
+        }
+    }
+
+    pub fn of(&self, piece: Piece) -> f64 {
+        match piece {
+            Piece::X => self.x,
+            Piece::O => self.o,
+            _ => 0.0,
+        }
+    }
+
+    pub fn tie(&self) -> f64 {
+        1.0 - self.x - self.o
+    }
+
+    // how much likelier piece is to win than to lose, which is what each player plays to raise
+    fn advantage(&self, piece: Piece) -> f64 {
+        self.of(piece) - self.of(piece.inverse())
+    }
+}
+
+// Solves random-turn games by expectiminimax: a position is worth the average of what it is worth
+// if X wins the coin flip and moves their best, and what it is worth if O does
+pub struct AiRandomTurn {
+    piece: Piece,
+    rules: Rules,
+    known_boards: HashMap<Board, WinChances>,
+}
+
+impl Display for AiRandomTurn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.piece.colorize("AI"), self.piece)
+    }
+}
+
+impl Player for AiRandomTurn {
+    fn propose_move(&mut self, game_board: &Board, _deadline: Option<Instant>) -> Action {
+        let scrambled = self.rules.standardized(ScrambledBoard::from(game_board.clone()));
+        let (_, options) = self.best_moves(&Board::from(&scrambled), self.piece);
+        let chosen_move = *options.choose(&mut thread_rng()).unwrap();
+        Action::Place(scrambled.space_at(chosen_move).unwrap().to_coord(), self.piece)
+    }
+
+    fn piece(&self) -> Piece {
+        self.piece
+    }
+
+    fn description(&self) -> String {
+        "Random-turn AI".to_string()
+    }
+}
+
+impl AiRandomTurn {
+    pub fn new(piece: Piece, rules: Rules) -> Self {
+        Self {
+            piece,
+            rules,
+            known_boards: HashMap::new(),
+        }
+    }
+
+    // each piece's chances of winning from b, before the coin is flipped for the next move
+    pub fn win_chances(&mut self, b: &Board) -> WinChances {
+        let key = Board::from(self.rules.standardized(ScrambledBoard::from(b.clone())));
+        if let Some(&chances) = self.known_boards.get(&key) {
+            return chances;
+        }
+
+        let chances = if let Some(winner) = self.winner(&key) {
+            WinChances::won_by(winner)
+        } else if key.is_full() {
+            WinChances { x: 0.0, o: 0.0 }
+        } else {
+            let (if_x, _) = self.best_moves(&key, Piece::X);
+            let (if_o, _) = self.best_moves(&key, Piece::O);
+            WinChances {
+                x: (if_x.x + if_o.x) / 2.0,
+                o: (if_x.o + if_o.o) / 2.0,
+            }
+        };
+        self.known_boards.insert(key, chances);
+        chances
+    }
+
+    // The chances after piece moves its best on b, and every space that is a best move. Of moves
+    // that leave the same advantage, the likelier win is better, so a sure tie loses to a coin flip
+    // between winning and losing.
+    fn best_moves(&mut self, b: &Board, piece: Piece) -> (WinChances, Vec<Coord>) {
+        let mut best: Option<(WinChances, Vec<Coord>)> = None;
+        for c in available_spaces(b) {
+            let mut child = b.clone();
+            child.place(piece, c.row, c.col).unwrap();
+            let chances = self.win_chances(&child);
+            let score = (chances.advantage(piece), chances.of(piece));
+
+            match &mut best {
+                Some((best_chances, options))
+                    if score == (best_chances.advantage(piece), best_chances.of(piece)) =>
+                {
+                    options.push(c)
+                }
+                Some((best_chances, _))
+                    if score < (best_chances.advantage(piece), best_chances.of(piece)) => {}
+                _ => best = Some((chances, vec![c])),
+            }
+        }
+        best.unwrap()
+    }
+
+    // the winner if the game is over on b, which only the piece placed last can have ended
+    fn winner(&self, b: &Board) -> Option<Piece> {
+        [Piece::X, Piece::O]
+            .into_iter()
+            .find(|&piece| self.rules.has_win(b, piece))
+            .and_then(|piece| self.rules.winner(b, piece))
+    }
+}
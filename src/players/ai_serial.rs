@@ -1,19 +1,28 @@
-use ciborium::{de, ser};
 use rand::seq::SliceRandom;
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::File;
+use std::time::Instant;
 
-use super::{available_spaces, MoveAnalysis, MoveValue, Player};
+use super::checkpoint::{self, Checkpoint};
+use super::{
+    available_moves, key_for, move_deadline, piece_from_key, place_for_key, read_inverted_strategy,
+    read_strategy, write_strategy, Action, MoveAnalysis, MoveValue, Player,
+};
+use crate::board::format_dimensions;
+use crate::rules::Rules;
 use crate::space::{Coord, Piece};
+use crate::strategy::StrategyError;
 use crate::Board;
 use crate::ScrambledBoard;
 
 pub struct AiSerial {
-    size: usize,
+    rows: usize,
+    cols: usize,
     piece: Piece,
     depth: usize,
+    rules: Rules,
     known_boards: HashMap<Board, MoveAnalysis>,
+    checkpoint: Option<Checkpoint>,
 }
 
 impl Display for AiSerial {
@@ -23,53 +32,99 @@ impl Display for AiSerial {
 }
 
 impl Player for AiSerial {
-    fn make_move(&mut self, game_board: &mut Board) {
-        assert_eq!(game_board.size, self.size);
+    fn propose_move(&mut self, game_board: &Board, deadline: Option<Instant>) -> Action {
+        assert_eq!((game_board.rows, game_board.cols), (self.rows, self.cols));
 
-        let chosen_move = self.choose_move(self.piece, game_board);
-        game_board
-            .place(self.piece(), chosen_move.row, chosen_move.col)
-            .unwrap();
+        let (coord, piece) = self.choose_move(self.piece, game_board, deadline);
+        Action::Place(coord, piece)
     }
 
     fn piece(&self) -> Piece {
         self.piece
     }
+
+    fn description(&self) -> String {
+        format!("Serial AI (depth {})", self.depth)
+    }
+
+    fn accept_swap(&mut self, game_board: &Board) -> bool {
+        let evaluation = self.evaluate(self.piece, game_board);
+        evaluation.for_parent() > evaluation
+    }
+
+    fn save_strategy(&mut self) -> std::io::Result<()> {
+        AiSerial::save_strategy(self)
+    }
 }
 
 impl AiSerial {
-    pub fn new(size: usize, piece: Piece, depth: usize) -> Self {
-        let depth = if depth > size * size {
-            size * size
+    pub fn new(rows: usize, cols: usize, piece: Piece, depth: usize, rules: Rules) -> Self {
+        let depth = if depth > rows * cols {
+            rows * cols
         } else {
             depth
         };
 
         Self {
-            size,
+            rows,
+            cols,
             piece,
             depth,
+            rules,
             known_boards: HashMap::new(),
+            checkpoint: None,
         }
     }
 
-    fn choose_move(&mut self, piece_to_play: Piece, game_board: &Board) -> Coord {
-        let mut scrambled = ScrambledBoard::from(game_board.clone());
-        if piece_to_play != self.piece {
-            // if asking to play a different piece than known_boards assumes
-            scrambled.invert();
-        }
-        scrambled.standardize();
+    fn choose_move(
+        &mut self,
+        piece_to_play: Piece,
+        game_board: &Board,
+        deadline: Option<Instant>,
+    ) -> (Coord, Piece) {
+        let scrambled = key_for(piece_to_play, self.piece, game_board, &self.rules);
 
         let key = Board::from(&scrambled);
 
-        let analysis = self.analyze(&key, self.depth);
+        let analysis = match deadline {
+            Some(deadline) => self.analyze_until(&key, move_deadline(game_board, deadline)),
+            None => self.analyze(&key, self.depth),
+        };
 
-        let chosen_move = analysis
+        let &(chosen_move, piece) = analysis
             .move_options
             .choose(&mut rand::thread_rng())
             .unwrap();
-        scrambled.space_at(chosen_move.clone()).unwrap().to_coord()
+        (
+            scrambled.space_at(chosen_move).unwrap().to_coord(),
+            piece_from_key(piece, self.piece, piece_to_play),
+        )
+    }
+
+    pub fn evaluate(&mut self, piece_to_play: Piece, game_board: &Board) -> MoveValue {
+        let scrambled = key_for(piece_to_play, self.piece, game_board, &self.rules);
+        self.analyze(&Board::from(scrambled), self.depth).evaluation
+    }
+
+    // Searches one move deeper at a time, up to self.depth, as long as the next search looks
+    // likely to finish before the deadline
+    fn analyze_until(&mut self, b: &Board, deadline: Instant) -> MoveAnalysis {
+        let mut analysis = self.analyze(b, 1);
+        for depth in 2..=self.depth {
+            if analysis.evaluation.is_known() {
+                break;
+            }
+
+            // each extra move multiplies the work by about the number of empty spaces
+            let started = Instant::now();
+            let next = self.analyze(b, depth);
+            let estimate = started.elapsed() * b.count(Piece::Empty) as u32;
+            analysis = next;
+            if Instant::now() + estimate > deadline {
+                break;
+            }
+        }
+        analysis
     }
 
     fn analyze(&mut self, b: &Board, depth_to_use: usize) -> MoveAnalysis {
@@ -81,55 +136,42 @@ impl AiSerial {
             }
         }
 
-        if b.has_win(self.piece.inverse()) {
-            // b already has other player winning
-            let new_analysis = MoveAnalysis {
-                evaluation: MoveValue::Lose(0),
-                move_options: vec![],
-                depth_used: self.depth, // max depth because no need to ever reanalyze this position deeper
-            };
-            self.known_boards.insert(b.clone(), new_analysis.clone());
-            return new_analysis;
-        }
-
-        if b.is_full() {
+        if let Some(evaluation) = self.rules.terminal_value(b, self.piece) {
+            // game already over
             let new_analysis = MoveAnalysis {
-                evaluation: MoveValue::Tie(0),
+                evaluation,
                 move_options: vec![],
                 depth_used: self.depth, // max depth because no need to ever reanalyze this position deeper
             };
-            self.known_boards.insert(b.clone(), new_analysis.clone());
+            self.remember(b, &new_analysis);
             return new_analysis;
         }
 
         if depth_to_use == 0 {
             let new_analysis = MoveAnalysis {
                 evaluation: MoveValue::Unknown(0),
-                move_options: available_spaces(b),
+                move_options: available_moves(b, self.piece, &self.rules),
                 depth_used: 0,
             };
-            self.known_boards.insert(b.clone(), new_analysis.clone());
+            self.remember(b, &new_analysis);
             return new_analysis;
         }
 
         // recursive case
-        let mut new_analyses: Vec<(Coord, MoveAnalysis)> = Vec::new();
-        available_spaces(b).into_iter().for_each(|c| {
-            let mut b = b.clone();
-            b.place(self.piece, c.row, c.col).unwrap();
-            b.invert();
+        let mut new_analyses: Vec<((Coord, Piece), MoveAnalysis)> = Vec::new();
+        available_moves(b, self.piece, &self.rules).into_iter().for_each(|m| {
+            let (b, turn_passed) = place_for_key(b, self.piece, m, &self.rules);
+            let can_swap = self.rules.can_swap(&b);
             let mut scrambled = ScrambledBoard::from(b);
-            scrambled.standardize();
+            self.rules.standardize(&mut scrambled);
             let mut lower_analysis = self.analyze(&Board::from(scrambled), depth_to_use - 1);
 
-            lower_analysis.evaluation = match lower_analysis.evaluation {
-                MoveValue::Lose(v) => MoveValue::Win(v + 1),
-                MoveValue::Tie(v) => MoveValue::Tie(v + 1),
-                MoveValue::Unknown(v) => MoveValue::Unknown(v + 1),
-                MoveValue::Win(v) => MoveValue::Lose(v + 1),
-            };
+            if can_swap {
+                lower_analysis.evaluation = lower_analysis.evaluation.with_swap();
+            }
+            lower_analysis.evaluation = lower_analysis.evaluation.for_mover(turn_passed);
 
-            new_analyses.push((c, lower_analysis));
+            new_analyses.push((m, lower_analysis));
         });
         let shallowest_depth = new_analyses.iter().map(|a| a.1.depth_used).min().unwrap();
         let depth_used = shallowest_depth + 1;
@@ -152,11 +194,18 @@ impl AiSerial {
             move_options,
             depth_used,
         };
-        self.known_boards.insert(b.clone(), new_analysis.clone());
+        self.remember(b, &new_analysis);
 
         new_analysis
     }
 
+    fn remember(&mut self, b: &Board, analysis: &MoveAnalysis) {
+        self.known_boards.insert(b.clone(), analysis.clone());
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.record(b, analysis);
+        }
+    }
+
     pub fn cbor_path(&self, inverted: bool) -> String {
         let p = if inverted {
             self.piece.inverse()
@@ -170,32 +219,57 @@ impl AiSerial {
             _ => "_",
         };
         format!(
-            "strategies/serial-s{}-p{}-d{}.cbor",
-            self.size, piece_str, self.depth
+            "strategies/serial-s{}-p{}-d{}{}.cbor",
+            format_dimensions(self.rows, self.cols),
+            piece_str,
+            self.depth,
+            self.rules.file_suffix()
         )
     }
 
-    pub fn save_strategy(&self) {
-        let buffer = File::create(&self.cbor_path(false)).unwrap(); // TODO: make safe
-        ser::into_writer(&self.known_boards, buffer).unwrap();
+    pub fn checkpoint_path(&self) -> String {
+        self.cbor_path(false).replace(".cbor", ".log")
+    }
+
+    // resumes from any existing checkpoint, then logs every new analysis to it
+    pub fn start_checkpointing(&mut self) {
+        let path = self.checkpoint_path();
+        if let Some(count) = checkpoint::replay(&path, &mut self.known_boards) {
+            for analysis in self.known_boards.values_mut() {
+                analysis.fill_in_pieces(self.piece);
+            }
+            println!("Resumed {} positions from {}", count, path);
+        }
+        self.checkpoint = Some(Checkpoint::open(&path).unwrap()); // TODO: make safe
+    }
+
+    pub fn save_strategy(&mut self) -> std::io::Result<()> {
+        write_strategy(&self.cbor_path(false), &self.known_boards)?;
         println!("Saved strategy to {}", self.cbor_path(false));
+
+        // everything in the checkpoint is now in the saved strategy
+        if let Some(checkpoint) = &mut self.checkpoint {
+            checkpoint.clear();
+        }
+        Ok(())
     }
 
-    pub fn load_strategy(&mut self) -> Option<()> {
-        if let Ok(f) = File::open(self.cbor_path(false)) {
-            self.known_boards = de::from_reader(f).unwrap();
+    // reads any saved strategy, returning whether there was one
+    pub fn load_strategy(&mut self) -> Result<bool, StrategyError> {
+        if let Some(known_boards) = read_strategy(&self.cbor_path(false))? {
+            self.known_boards = known_boards;
             println!("Read strategy from {}", self.cbor_path(false));
-            Some(())
-        } else if let Ok(f) = File::open(self.cbor_path(true)) {
-            let known_boards_inverted: HashMap<Board, MoveAnalysis> = de::from_reader(f).unwrap();
-            for b in known_boards_inverted.keys() {
-                let analysis = known_boards_inverted.get(b).unwrap();
-                self.known_boards.insert(b.inverse(), analysis.clone());
-            }
+        } else if let Some(known_boards) =
+            read_inverted_strategy(&self.cbor_path(true), self.piece)?
+        {
+            self.known_boards = known_boards;
             println!("Read strategy from {}", self.cbor_path(true));
-            Some(())
         } else {
-            None
+            return Ok(false);
+        }
+        for analysis in self.known_boards.values_mut() {
+            analysis.fill_in_pieces(self.piece);
         }
+        Ok(true)
     }
 }
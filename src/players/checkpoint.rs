@@ -0,0 +1,81 @@
+use ciborium::{de, ser};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::Board;
+
+// short, since release builds abort on panic without dropping, and so without a final flush
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Append-only log of known_boards entries, so an interrupted solve can be resumed.
+// Entries are written as consecutive CBOR (Board, analysis) pairs; later entries
+// for the same board supersede earlier ones.
+pub struct Checkpoint {
+    writer: BufWriter<File>,
+    last_flush: Instant,
+}
+
+impl Checkpoint {
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let f = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            writer: BufWriter::new(f),
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn record<V: Serialize>(&mut self, b: &Board, analysis: &V) {
+        ser::into_writer(&(b, analysis), &mut self.writer).expect("Failed to write checkpoint");
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.writer.flush().expect("Failed to write checkpoint");
+        self.last_flush = Instant::now();
+    }
+
+    // discard all logged entries, e.g. once they are covered by a saved strategy
+    pub fn clear(&mut self) {
+        self.flush();
+        self.writer.get_ref().set_len(0).expect("Failed to clear checkpoint");
+    }
+}
+
+impl Drop for Checkpoint {
+    fn drop(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+// Reads every complete entry from the log at path into known_boards, returning how many were read.
+// A truncated final entry (e.g. from a crash mid-write) is cut off the log, so that entries
+// appended after resuming follow straight on from the last complete one.
+pub fn replay<V: DeserializeOwned>(path: &str, known_boards: &mut HashMap<Board, V>) -> Option<usize> {
+    let bytes = fs::read(path).ok()?;
+    let mut remaining = &bytes[..];
+    let mut good_len = 0;
+    let mut count = 0;
+
+    while let Ok((b, analysis)) = de::from_reader::<(Board, V), _>(&mut remaining) {
+        known_boards.insert(b, analysis);
+        count += 1;
+        good_len = bytes.len() - remaining.len();
+    }
+
+    if good_len < bytes.len() {
+        let f = OpenOptions::new().write(true).open(path).ok()?;
+        f.set_len(good_len as u64).ok()?;
+    }
+    Some(count)
+}
@@ -1,22 +1,27 @@
 use std::fmt::Display;
+use std::time::Instant;
 
-use super::Player;
+use super::{Action, Player};
 use crate::user_input;
 use crate::{
     Board,
+    rules::Rules,
     space::Piece,
 };
 
 pub struct Human {
     pub name: String,
     pub piece: Piece,
+    // to know which pieces the human may place
+    pub rules: Rules,
 }
 
 impl Human {
-    pub fn new(name: &str, piece: Piece) -> Self {
+    pub fn new(name: &str, piece: Piece, rules: Rules) -> Self {
         Self {
             name: name.to_string(),
             piece,
+            rules,
         }
     }
 }
@@ -31,8 +36,19 @@ impl Player for Human {
     fn piece(&self) -> Piece {
         self.piece
     }
-    fn make_move(&mut self, game_board: &mut Board) {
-        let c = user_input::get_move(&self.name, game_board);
-        game_board.place(self.piece, c.row, c.col).expect("Move was not validated properly");
+
+    fn description(&self) -> String {
+        self.name.clone()
+    }
+    fn propose_move(&mut self, game_board: &Board, deadline: Option<Instant>) -> Action {
+        user_input::get_move(&self.name, self.piece, game_board, deadline, self.rules)
+    }
+
+    fn accept_draw(&mut self, _game_board: &Board) -> bool {
+        user_input::get_draw_response(&self.name)
+    }
+
+    fn accept_swap(&mut self, _game_board: &Board) -> bool {
+        user_input::get_swap_response(&self.name)
     }
 }
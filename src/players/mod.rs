@@ -1,13 +1,23 @@
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::Instant;
 
-use serde::{Deserialize, Serialize};
+use ciborium::{de, ser};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
 use crate::{
+    rules::Rules,
     space::{Coord, Piece},
-    Board,
+    strategy::{Strategy, StrategyError},
+    Board, ScrambledBoard,
 };
 
+mod checkpoint;
+
 mod human;
 pub use human::Human;
 
@@ -15,7 +25,7 @@ mod ai_serial;
 pub use ai_serial::AiSerial;
 
 mod ai_lazy;
-pub use ai_lazy::AiLazy;
+pub use ai_lazy::{AiLazy, LazyMoveAnalysis};
 
 mod ai_parallel;
 pub use ai_parallel::AiParallel;
@@ -26,9 +36,46 @@ pub use ai_random::AiRandom;
 mod ai_ground_up;
 pub use ai_ground_up::AiGroundUp;
 
+mod ai_random_turn;
+pub use ai_random_turn::{AiRandomTurn, WinChances};
+
+// something a player can ask the game for instead of making a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Undo,
+    Redo,
+    Save,
+}
+
+// What a player does on their turn; the game checks that any move is legal before making it.
+// The piece placed is the player's own unless the rules let them place either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Place(Coord, Piece),
+    Resign,
+    OfferDraw,
+    Request(Request),
+}
+
 pub trait Player: Display {
-    fn make_move(&mut self, game_board: &mut Board);
+    // deadline is when the player's clock runs out, if there is a clock. Players that can't stop
+    // thinking early may ignore it, and lose on time.
+    fn propose_move(&mut self, game_board: &Board, deadline: Option<Instant>) -> Action;
+    // whether to agree to a draw the opponent offered
+    fn accept_draw(&mut self, _game_board: &Board) -> bool {
+        false
+    }
+    // whether to swap sides under the pie rule, asked only when it is allowed
+    fn accept_swap(&mut self, _game_board: &Board) -> bool {
+        false
+    }
+    // saves whatever the player has worked out so far, for AIs that keep a strategy file
+    fn save_strategy(&mut self) -> io::Result<()> {
+        Ok(())
+    }
     fn piece(&self) -> Piece;
+    // plain-text description of the player, e.g. for game records
+    fn description(&self) -> String;
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -81,23 +128,201 @@ impl MoveValue {
             MoveValue::Win(v) => v,
         }
     }
+
+    // value of a position for the player who moved into it, given its value for the player to move
+    pub fn for_parent(&self) -> Self {
+        match *self {
+            MoveValue::Lose(v) => MoveValue::Win(v + 1),
+            MoveValue::Tie(v) => MoveValue::Tie(v + 1),
+            MoveValue::Unknown(v) => MoveValue::Unknown(v + 1),
+            MoveValue::Win(v) => MoveValue::Lose(v + 1),
+        }
+    }
+
+    // Value of a position for the player who placed the last piece, given its value for the
+    // player to move, who is the same player if the turn hasn't passed
+    pub fn for_mover(&self, turn_passed: bool) -> Self {
+        if turn_passed {
+            return self.for_parent();
+        }
+        match *self {
+            MoveValue::Lose(v) => MoveValue::Lose(v + 1),
+            MoveValue::Tie(v) => MoveValue::Tie(v + 1),
+            MoveValue::Unknown(v) => MoveValue::Unknown(v + 1),
+            MoveValue::Win(v) => MoveValue::Win(v + 1),
+        }
+    }
+
+    // value of a position for the player to move if they may swap sides instead of moving there
+    pub fn with_swap(&self) -> Self {
+        self.clone().max(self.for_parent())
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, MoveValue::Unknown(_))
+    }
+
+    // true if both values are known but disagree on whether the position is won, lost or tied
+    pub fn conflicts_with(&self, other: &MoveValue) -> bool {
+        self.is_known()
+            && other.is_known()
+            && std::mem::discriminant(self) != std::mem::discriminant(other)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoveAnalysis {
     pub evaluation: MoveValue,
-    pub move_options: Vec<Coord>,
+    // each a space and the piece to place there
+    #[serde(deserialize_with = "saved_moves")]
+    pub move_options: Vec<(Coord, Piece)>,
     pub depth_used: usize,
 }
 
-fn available_spaces(b: &Board) -> Vec<Coord> {
+impl MoveAnalysis {
+    // gives any moves read from a strategy saved with spaces alone the piece the strategy keys positions by
+    pub(crate) fn fill_in_pieces(&mut self, piece: Piece) {
+        for (_, p) in &mut self.move_options {
+            if *p == Piece::Empty {
+                *p = piece;
+            }
+        }
+    }
+}
+
+// Strategies saved before moves gave the piece to place have only the space, where the piece was
+// always that of the player to move. Such a move reads with an Empty piece until the strategy
+// fills in its own.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedMove {
+    Placed(Coord, Piece),
+    Space(Coord),
+}
+
+impl From<SavedMove> for (Coord, Piece) {
+    fn from(saved: SavedMove) -> Self {
+        match saved {
+            SavedMove::Placed(c, piece) => (c, piece),
+            SavedMove::Space(c) => (c, Piece::Empty),
+        }
+    }
+}
+
+pub(crate) fn saved_moves<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<(Coord, Piece)>, D::Error> {
+    let saved = Vec::<SavedMove>::deserialize(d)?;
+    Ok(saved.into_iter().map(Into::into).collect())
+}
+
+pub(crate) fn saved_move<'de, D: Deserializer<'de>>(d: D) -> Result<Option<(Coord, Piece)>, D::Error> {
+    let saved = Option::<SavedMove>::deserialize(d)?;
+    Ok(saved.map(Into::into))
+}
+
+// the strategy saved at path, or None if there is no file there
+pub(crate) fn read_strategy<V: DeserializeOwned>(
+    path: &str,
+) -> Result<Option<HashMap<Board, V>>, StrategyError> {
+    let f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    de::from_reader(f)
+        .map(Some)
+        .map_err(|e| StrategyError::Cbor(e.to_string()))
+}
+
+// The strategy the other piece saved at path, re-keyed with piece to move, or None if there is no
+// file there. Inverted boards are standardized again, so they match the boards piece looks up.
+pub(crate) fn read_inverted_strategy(
+    path: &str,
+    piece: Piece,
+) -> Result<Option<HashMap<Board, MoveAnalysis>>, StrategyError> {
+    let mut strategy = match Strategy::load(path) {
+        Ok(strategy) => strategy,
+        Err(StrategyError::Io(e)) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    strategy.set_piece(piece);
+    Ok(Some(strategy.known_boards))
+}
+
+// saves a strategy to path, creating its directory if need be
+pub(crate) fn write_strategy<V: Serialize>(
+    path: &str,
+    known_boards: &HashMap<Board, V>,
+) -> io::Result<()> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let buffer = File::create(path)?;
+    ser::into_writer(known_boards, buffer).map_err(|e| match e {
+        ser::Error::Io(e) => e,
+        ser::Error::Value(message) => io::Error::new(ErrorKind::InvalidData, message),
+    })
+}
+
+pub(crate) fn available_spaces(b: &Board) -> Vec<Coord> {
     let mut result = Vec::new();
-    for row in 0..b.size {
-        for col in 0..b.size {
+    for row in 0..b.rows {
+        for col in 0..b.cols {
             if b.piece_at(row, col) == Ok(Piece::Empty) {
                 result.push(Coord { row, col })
             }
         }
     }
     result
-}
\ No newline at end of file
+}
+
+// every move mover can make on b: each empty space, with each piece the rules let them place
+pub(crate) fn available_moves(b: &Board, mover: Piece, rules: &Rules) -> Vec<(Coord, Piece)> {
+    let pieces = rules.pieces_for(mover);
+    available_spaces(b)
+        .into_iter()
+        .flat_map(|c| pieces.iter().map(move |&p| (c, p)))
+        .collect()
+}
+
+// A game board with piece_to_play to move as a solver keys it, where own is the piece the solver
+// keys positions with: inverted if the two differ, then standardized. Its spaces map moves on the
+// key back to the game board.
+pub(crate) fn key_for(piece_to_play: Piece, own: Piece, game_board: &Board, rules: &Rules) -> ScrambledBoard {
+    let mut scrambled = ScrambledBoard::from(game_board.clone());
+    if piece_to_play != own {
+        scrambled.invert();
+    }
+    rules.standardize(&mut scrambled);
+    scrambled
+}
+
+// The piece to place in the game for a piece to place in a solver's key, where own is the piece
+// the solver keys positions with and to_play the piece actually to move
+pub(crate) fn piece_from_key(piece: Piece, own: Piece, to_play: Piece) -> Piece {
+    if own == to_play {
+        piece
+    } else {
+        piece.inverse()
+    }
+}
+
+// b after mover makes move m, the way the solvers key positions: inverted if the turn passes to
+// the other player, so that mover is to move either way. Also returns whether the turn passed.
+pub(crate) fn place_for_key(b: &Board, mover: Piece, m: (Coord, Piece), rules: &Rules) -> (Board, bool) {
+    let (c, piece) = m;
+    let mut child = b.clone();
+    child.place(piece, c.row, c.col).unwrap();
+    let turn_passed = rules.next_to_move(&child, mover) != mover;
+    if turn_passed {
+        child.invert();
+    }
+    (child, turn_passed)
+}
+
+// The time to aim to move by, given when the clock runs out: an even share of what is left for
+// each of this player's remaining moves
+pub(crate) fn move_deadline(b: &Board, deadline: Instant) -> Instant {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let moves_left = b.count(Piece::Empty).div_ceil(2).max(1);
+    Instant::now() + remaining / moves_left as u32
+}
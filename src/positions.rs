@@ -0,0 +1,113 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::HashSet;
+
+use crate::players::{available_moves, available_spaces};
+use crate::rules::Rules;
+use crate::space::Piece;
+use crate::win_condition::WinCondition;
+use crate::{Board, ScrambledBoard};
+
+const MAX_ATTEMPTS: usize = 10_000;
+
+// X always moves first, so with one piece per turn X is to play whenever the counts are equal
+pub fn piece_to_play(b: &Board, rules: Rules) -> Piece {
+    rules.piece_placing(rules.placed(b), Piece::X)
+}
+
+// One standardized board for each class of unfinished positions with the given number of moves made,
+// where which boards are equivalent and which are finished depend on the goal, and how many pieces
+// each player has on the bias
+pub fn canonical_positions(rows: usize, cols: usize, moves: usize, rules: Rules) -> Vec<Board> {
+    let mut level = vec![Board::new(rows, cols)];
+
+    for _ in 0..moves {
+        let mut next_level = HashSet::new();
+        for b in &level {
+            let mover = piece_to_play(b, rules);
+            for (c, piece) in available_moves(b, mover, &rules) {
+                let mut child = b.clone();
+                child.place(piece, c.row, c.col).unwrap();
+                // only the piece just placed can have met the goal, which may not count for it
+                if rules.winner(&child, mover).is_none() {
+                    let mut scrambled = ScrambledBoard::from(child);
+                    rules.standardize(&mut scrambled);
+                    next_level.insert(Board::from(scrambled));
+                }
+            }
+        }
+        level = next_level.into_iter().collect();
+    }
+
+    // sorted so that seeded generators are reproducible
+    level.sort_unstable();
+    level
+}
+
+// Generates random unfinished positions: the right number of X's and O's for the move number, or
+// any mix of them if either player may place either, and neither side having met the goal in a
+// way that counts
+pub struct PositionGenerator<R: Rng = StdRng> {
+    rows: usize,
+    cols: usize,
+    rules: Rules,
+    rng: R,
+}
+
+impl PositionGenerator {
+    pub fn new(rows: usize, cols: usize, rules: Rules) -> Self {
+        Self::with_rng(rows, cols, rules, StdRng::from_entropy())
+    }
+
+    pub fn seeded(rows: usize, cols: usize, rules: Rules, seed: u64) -> Self {
+        Self::with_rng(rows, cols, rules, StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> PositionGenerator<R> {
+    pub fn with_rng(rows: usize, cols: usize, rules: Rules, rng: R) -> Self {
+        Self {
+            rows,
+            cols,
+            rules,
+            rng,
+        }
+    }
+
+    // Uniform over all such boards. None if no such board turned up, e.g. because there is none.
+    pub fn random_position(&mut self, moves: usize) -> Option<Board> {
+        if moves > self.rows * self.cols {
+            return None;
+        }
+
+        let all_spaces = available_spaces(&Board::new(self.rows, self.cols));
+        for _ in 0..MAX_ATTEMPTS {
+            let mut b = Board::new(self.rows, self.cols);
+            let chosen = all_spaces.choose_multiple(&mut self.rng, moves);
+            for (i, c) in chosen.enumerate() {
+                let pieces = self.rules.pieces_for(self.rules.piece_placing(i, Piece::X));
+                let piece = *pieces.choose(&mut self.rng).unwrap();
+                b.place(piece, c.row, c.col).unwrap();
+            }
+
+            // a Breaker's line doesn't end the game
+            let breaker = self.rules.maker(&b, piece_to_play(&b, self.rules)).map(|m| m.inverse());
+            let won = |piece| breaker != Some(piece) && self.rules.has_win(&b, piece);
+            if !won(Piece::X) && !won(Piece::O) {
+                return Some(b);
+            }
+        }
+
+        None
+    }
+
+    // Uniform over standardized classes rather than boards, returned as a random member of its class
+    pub fn random_canonical_position(&mut self, moves: usize) -> Option<Board> {
+        let canonical = canonical_positions(self.rows, self.cols, moves, self.rules)
+            .choose(&mut self.rng)?
+            .clone();
+        Some(self.rules.goal.scramble(&canonical, &mut self.rng))
+    }
+}
+
@@ -0,0 +1,587 @@
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+use crate::board::{GridError, ParseBoardError};
+use crate::game::{side_to_move, DrawReason, Game, IllegalMove, Outcome, PositionError, WinReason};
+use crate::rules::{ParseRulesError, Rules};
+use crate::space::{format_move, parse_move, Coord, ParseCoordError, Piece};
+use crate::{format_dimensions, parse_dimensions, Board};
+
+// A finished or in-progress game, saved as text such as
+//
+//     # comments start with '#'
+//     Size: 3
+//     X: Player X
+//     O: Lazy AI
+//     Result: O
+//     Moves: A1 B2 A2 A3 C1 B1 C2 C3
+//
+// Result is X, O, Tie, or * for a game still in progress. A game that didn't end with the goal
+// met or a full board also has a Termination field: resignation, time, agreement, or
+// illegal followed by the move the loser tried to make, e.g. "Termination: illegal A1". Moves
+// may be split across several Moves lines. A game that didn't start from an empty board also has the fields
+//
+//     Start: X../.O./..X
+//     First: O
+//
+// giving the board at the start, in Board's notation, and who moved first from there. First may
+// be left out if it follows from the number of each piece, as for a game where X moved first.
+// Size is written as a single number for a square board, or as rows by columns for a rectangular
+// one, e.g. "Size: 3x4". Size may be left out if Start is given. Games played under rules other than the standard ones
+// have a Rules field, e.g. "Rules: misere" or "Rules: misere 3-in-a-row". Under the pie rule, a
+// swap of sides is written as "swap" among the moves, e.g. "Moves: B2 swap A1". In an
+// Order-and-Chaos game each move also gives the piece placed, e.g. "Moves: B2=O A1=X", as it
+// does in a random-turn game, where the piece placed is that of whoever won the coin flip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub rows: usize,
+    pub cols: usize,
+    pub rules: Rules,
+    pub x_player: String,
+    pub o_player: String,
+    pub start: Board,
+    pub first: Piece,
+    // each a space and the piece placed there
+    pub moves: Vec<(Coord, Piece)>,
+    // whether a player swapped sides under the pie rule, which happens before move swap_point()
+    pub swapped: bool,
+    pub result: Option<Outcome>,
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordError {
+    Io(String),
+    Syntax { line: usize, text: String },
+    UnknownField { line: usize, field: String },
+    DuplicateField { line: usize, field: String },
+    MissingField(&'static str),
+    InvalidSize { line: usize, text: String },
+    InvalidResult { line: usize, text: String },
+    InvalidTermination { line: usize, text: String },
+    // the result doesn't match the final board, e.g. a win without the goal met
+    ResultMismatch { line: usize },
+    InvalidPiece { line: usize, text: String },
+    InvalidRules { line: usize, error: ParseRulesError },
+    InvalidBoard { line: usize, error: ParseBoardError },
+    // rows and columns of the Size field and of the starting board
+    StartSizeMismatch { line: usize, size: (usize, usize), start_size: (usize, usize) },
+    InvalidStart(PositionError),
+    InvalidMove { line: usize, error: ParseCoordError },
+    IllegalMove { line: usize, coord: Coord, error: GridError },
+    MoveAfterEnd { line: usize, coord: Coord },
+    // a swap where the pie rule doesn't allow one
+    InvalidSwap { line: usize },
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Io(e) => write!(f, "{}", e),
+            RecordError::Syntax { line, text } => {
+                write!(f, "line {}: expected \"Field: value\", found \"{}\"", line, text)
+            }
+            RecordError::UnknownField { line, field } => {
+                write!(f, "line {}: unknown field \"{}\"", line, field)
+            }
+            RecordError::DuplicateField { line, field } => {
+                write!(f, "line {}: field \"{}\" given more than once", line, field)
+            }
+            RecordError::MissingField(field) => write!(f, "missing field \"{}\"", field),
+            RecordError::InvalidSize { line, text } => {
+                write!(f, "line {}: invalid board size \"{}\"", line, text)
+            }
+            RecordError::InvalidResult { line, text } => {
+                write!(
+                    f,
+                    "line {}: invalid result \"{}\", expected X, O, Tie or *",
+                    line, text
+                )
+            }
+            RecordError::InvalidTermination { line, text } => {
+                write!(f, "line {}: invalid termination \"{}\" for this result", line, text)
+            }
+            RecordError::ResultMismatch { line } => {
+                write!(f, "line {}: result doesn't match the final board", line)
+            }
+            RecordError::InvalidRules { line, error } => write!(f, "line {}: {}", line, error),
+            RecordError::InvalidPiece { line, text } => {
+                write!(f, "line {}: invalid piece \"{}\", expected X or O", line, text)
+            }
+            RecordError::InvalidBoard { line, error } => write!(f, "line {}: {}", line, error),
+            RecordError::StartSizeMismatch {
+                line,
+                size,
+                start_size,
+            } => {
+                write!(
+                    f,
+                    "line {}: starting board is of size {}, but Size is {}",
+                    line,
+                    format_dimensions(start_size.0, start_size.1),
+                    format_dimensions(size.0, size.1)
+                )
+            }
+            RecordError::InvalidStart(e) => write!(f, "invalid starting position: {}", e),
+            RecordError::InvalidMove { line, error } => write!(f, "line {}: {}", line, error),
+            RecordError::IllegalMove { line, coord, error } => {
+                write!(f, "line {}: illegal move {}: {}", line, coord, error)
+            }
+            RecordError::MoveAfterEnd { line, coord } => {
+                write!(f, "line {}: move {} made after the game was won", line, coord)
+            }
+            RecordError::InvalidSwap { line } => {
+                write!(f, "line {}: swap made where the rules don't allow it", line)
+            }
+        }
+    }
+}
+
+impl GameRecord {
+    pub fn from_game(game: &Game) -> Self {
+        let (x_player, o_player) = if game.p1().piece() == Piece::X {
+            (game.p1(), game.p2())
+        } else {
+            (game.p2(), game.p1())
+        };
+
+        Self {
+            rows: game.board().rows,
+            cols: game.board().cols,
+            rules: game.rules(),
+            x_player: x_player.description(),
+            o_player: o_player.description(),
+            start: game.start().clone(),
+            first: game.first_piece(),
+            moves: game.history().to_vec(),
+            swapped: game.swapped(),
+            result: game.outcome().cloned(),
+            comments: vec![],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, RecordError> {
+        fs::read_to_string(path)
+            .map_err(|e| RecordError::Io(e.to_string()))?
+            .parse()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), RecordError> {
+        fs::write(path, self.to_string()).map_err(|e| RecordError::Io(e.to_string()))
+    }
+
+    // the piece that made move i, counting from 0
+    pub fn piece_moving(&self, i: usize) -> Piece {
+        match self.moves.get(i) {
+            // under random-turn rules everyone places their own piece, whenever the coin says
+            Some(&(_, piece)) if self.rules.random_turn => piece,
+            _ => piece_moving(&self.start, self.first, self.rules, self.swap_at(), i),
+        }
+    }
+
+    // the number of moves made when a player may swap sides under the pie rule, if the rules and
+    // the start position leave that chance
+    pub fn swap_point(&self) -> Option<usize> {
+        swap_point(&self.start, self.rules)
+    }
+
+    fn swap_at(&self) -> Option<usize> {
+        self.swap_point().filter(|_| self.swapped)
+    }
+
+    // the board after the first n moves, along with any swap made before the next one
+    pub fn board_after(&self, n: usize) -> Board {
+        let mut b = self.start.clone();
+        for (i, &(c, piece)) in self.moves.iter().take(n).enumerate() {
+            if self.swap_at() == Some(i) {
+                b.invert();
+            }
+            b.place(piece, c.row, c.col).unwrap();
+        }
+        if self.swap_at() == Some(n) {
+            b.invert();
+        }
+        b
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for comment in &self.comments {
+            writeln!(f, "# {}", comment)?;
+        }
+        writeln!(f, "Size: {}", format_dimensions(self.rows, self.cols))?;
+        if self.rules != Rules::default() {
+            writeln!(f, "Rules: {}", self.rules)?;
+        }
+        writeln!(f, "X: {}", self.x_player)?;
+        writeln!(f, "O: {}", self.o_player)?;
+
+        if self.start != Board::new(self.rows, self.cols) || self.first != Piece::X {
+            writeln!(f, "Start: {}", self.start)?;
+            // not the piece's own Display, which is colored
+            let first = if self.first == Piece::X { "X" } else { "O" };
+            writeln!(f, "First: {}", first)?;
+        }
+
+        let result = match &self.result {
+            Some(Outcome::Win { piece: Piece::X, .. }) => "X",
+            Some(Outcome::Win { .. }) => "O",
+            Some(Outcome::Draw(_)) => "Tie",
+            None => "*",
+        };
+        writeln!(f, "Result: {}", result)?;
+
+        let termination = match &self.result {
+            Some(Outcome::Win { reason, .. }) => match reason {
+                WinReason::Goal(_) | WinReason::GoalNeverMet => None,
+                WinReason::Resignation => Some("resignation".to_string()),
+                WinReason::Timeout => Some("time".to_string()),
+                WinReason::IllegalMove(IllegalMove {
+                    coord,
+                    error: GridError::PieceNotAllowed { piece },
+                    ..
+                }) => Some(format!("illegal {}", format_move(*coord, *piece))),
+                WinReason::IllegalMove(illegal) => Some(format!("illegal {}", illegal.coord)),
+            },
+            Some(Outcome::Draw(DrawReason::Agreement)) => Some("agreement".to_string()),
+            Some(Outcome::Draw(DrawReason::BoardFull)) | None => None,
+        };
+        if let Some(termination) = termination {
+            writeln!(f, "Termination: {}", termination)?;
+        }
+
+        let mut moves: Vec<String> = self
+            .moves
+            .iter()
+            .map(|&(c, piece)| {
+                if self.rules.order_chaos.is_some() || self.rules.random_turn {
+                    format_move(c, piece)
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect();
+        if let Some(i) = self.swap_at() {
+            moves.insert(i, "swap".to_string());
+        }
+        writeln!(f, "Moves: {}", moves.join(" "))
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut size = None;
+        let mut rules = None;
+        let mut x_player = None;
+        let mut o_player = None;
+        let mut result = None;
+        let mut termination = None;
+        let mut first = None;
+        let mut start = None;
+        let mut moves = Vec::new();
+        let mut swap = None;
+        let mut comments = Vec::new();
+
+        for (i, text) in s.lines().enumerate() {
+            let line = i + 1;
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            if let Some(comment) = text.strip_prefix('#') {
+                comments.push(comment.trim().to_string());
+                continue;
+            }
+
+            let Some((field, value)) = text.split_once(':') else {
+                return Err(RecordError::Syntax {
+                    line,
+                    text: text.to_string(),
+                });
+            };
+            let (field, value) = (field.trim(), value.trim());
+            let duplicate = || RecordError::DuplicateField {
+                line,
+                field: field.to_string(),
+            };
+
+            match field.to_lowercase().as_str() {
+                "size" => {
+                    let parsed = parse_dimensions(value).ok_or_else(|| RecordError::InvalidSize {
+                        line,
+                        text: value.to_string(),
+                    })?;
+                    if size.replace(parsed).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "x" => {
+                    if x_player.replace(value.to_string()).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "o" => {
+                    if o_player.replace(value.to_string()).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "result" => {
+                    let parsed = match value.to_lowercase().as_str() {
+                        "x" => Some(Some(Piece::X)),
+                        "o" => Some(Some(Piece::O)),
+                        "tie" => Some(None),
+                        "*" => None,
+                        _ => {
+                            return Err(RecordError::InvalidResult {
+                                line,
+                                text: value.to_string(),
+                            })
+                        }
+                    };
+                    if result.replace((line, parsed)).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "termination" => {
+                    if termination.replace((line, value.to_lowercase())).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "rules" => {
+                    let parsed = value
+                        .parse()
+                        .map_err(|error| RecordError::InvalidRules { line, error })?;
+                    if rules.replace(parsed).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "first" => {
+                    let parsed = match value.to_lowercase().as_str() {
+                        "x" => Piece::X,
+                        "o" => Piece::O,
+                        _ => {
+                            return Err(RecordError::InvalidPiece {
+                                line,
+                                text: value.to_string(),
+                            })
+                        }
+                    };
+                    if first.replace(parsed).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "start" => {
+                    let parsed: Board = value
+                        .parse()
+                        .map_err(|error| RecordError::InvalidBoard { line, error })?;
+                    if start.replace((line, parsed)).is_some() {
+                        return Err(duplicate());
+                    }
+                }
+                "moves" => {
+                    for token in value.split_whitespace() {
+                        if token.eq_ignore_ascii_case("swap") {
+                            if swap.replace((line, moves.len())).is_some() {
+                                return Err(RecordError::InvalidSwap { line });
+                            }
+                            continue;
+                        }
+                        let (coord, piece) = parse_move(token)
+                            .map_err(|error| RecordError::InvalidMove { line, error })?;
+                        moves.push((line, coord, piece));
+                    }
+                }
+                _ => {
+                    return Err(RecordError::UnknownField {
+                        line,
+                        field: field.to_string(),
+                    })
+                }
+            }
+        }
+
+        let ((rows, cols), start) = match (size, start) {
+            (Some((rows, cols)), None) => ((rows, cols), Board::new(rows, cols)),
+            (None, Some((_, start))) => ((start.rows, start.cols), start),
+            (Some(size), Some((line, start))) if (start.rows, start.cols) != size => {
+                return Err(RecordError::StartSizeMismatch {
+                    line,
+                    size,
+                    start_size: (start.rows, start.cols),
+                })
+            }
+            (Some(size), Some((_, start))) => (size, start),
+            (None, None) => return Err(RecordError::MissingField("Size")),
+        };
+        let rules = rules.unwrap_or_default();
+        let first = side_to_move(&start, first, rules).map_err(RecordError::InvalidStart)?;
+
+        // the swap has to come right when the pie rule allows one
+        if let Some((line, i)) = swap {
+            if swap_point(&start, rules) != Some(i) {
+                return Err(RecordError::InvalidSwap { line });
+            }
+        }
+        let swap_at = swap.map(|(_, i)| i);
+
+        // replay the moves to make sure they could have happened
+        let mut b = start.clone();
+        let mut placed = Vec::new();
+        let mut won = false;
+        for i in 0..=moves.len() {
+            if let Some((line, _)) = swap.filter(|&(_, swap_at)| swap_at == i) {
+                if !rules.can_swap(&b) {
+                    return Err(RecordError::InvalidSwap { line });
+                }
+                b.invert();
+            }
+
+            let Some(&(line, coord, piece)) = moves.get(i) else {
+                break;
+            };
+            if won {
+                return Err(RecordError::MoveAfterEnd { line, coord });
+            }
+            let mover = match piece {
+                Some(piece) if rules.random_turn => piece,
+                _ => piece_moving(&start, first, rules, swap_at, i),
+            };
+            let piece = piece.unwrap_or(mover);
+            if !rules.pieces_for(mover).contains(&piece) {
+                let error = GridError::PieceNotAllowed { piece };
+                return Err(RecordError::IllegalMove { line, coord, error });
+            }
+            b.place(piece, coord.row, coord.col)
+                .map_err(|error| RecordError::IllegalMove { line, coord, error })?;
+            placed.push((coord, piece));
+            won = rules.winner(&b, mover).is_some();
+        }
+
+        let result = match result {
+            Some((line, Some(winner))) => {
+                let to_move = piece_moving(&start, first, rules, swap_at, moves.len());
+                let mover = match placed.last() {
+                    Some(&(_, piece)) if rules.random_turn => piece,
+                    _ => rules.last_mover(&b, to_move),
+                };
+                Some(outcome_from_str(&b, to_move, mover, rules, line, winner, termination)?)
+            }
+            _ => {
+                if let Some((line, text)) = termination {
+                    return Err(RecordError::InvalidTermination { line, text });
+                }
+                None
+            }
+        };
+
+        Ok(Self {
+            rows,
+            cols,
+            rules,
+            x_player: x_player.unwrap_or_default(),
+            o_player: o_player.unwrap_or_default(),
+            start,
+            first,
+            moves: placed,
+            swapped: swap.is_some(),
+            result,
+            comments,
+        })
+    }
+}
+
+// The piece that makes move i from start, counting from 0, given the piece that makes the first
+// and the number of moves made before a swap, if there was one
+fn piece_moving(start: &Board, first: Piece, rules: Rules, swap_at: Option<usize>, i: usize) -> Piece {
+    let mut first = rules.first_player(start, first);
+    if swap_at.is_some_and(|swap_at| i >= swap_at) {
+        first = first.inverse();
+    }
+    rules.piece_placing(rules.placed(start) + i, first)
+}
+
+fn swap_point(start: &Board, rules: Rules) -> Option<usize> {
+    if !rules.pie {
+        return None;
+    }
+    rules.bias.first.checked_sub(rules.placed(start))
+}
+
+// The outcome of a finished game given its final board, who would have moved next and who moved
+// last, winning piece (None for a tie), and the line and text of its Termination field, if any
+fn outcome_from_str(
+    b: &Board,
+    to_move: Piece,
+    mover: Piece,
+    rules: Rules,
+    line: usize,
+    winner: Option<Piece>,
+    termination: Option<(usize, String)>,
+) -> Result<Outcome, RecordError> {
+    let Some(piece) = winner else {
+        return match termination {
+            None if b.is_full() && rules.full_board_winner(b, to_move).is_none() => {
+                Ok(Outcome::Draw(DrawReason::BoardFull))
+            }
+            None => Err(RecordError::ResultMismatch { line }),
+            Some((_, text)) if text == "agreement" => Ok(Outcome::Draw(DrawReason::Agreement)),
+            Some((line, text)) => Err(RecordError::InvalidTermination { line, text }),
+        };
+    };
+
+    let reason = match termination {
+        None => {
+            // the last move met the goal and so gave piece the win, or filled the board
+            if rules.winner(b, mover) == Some(piece) {
+                // under Order and Chaos the mover may have met the goal with the other piece
+                let spaces = rules
+                    .winning_spaces(b, mover)
+                    .or_else(|| rules.winning_spaces(b, mover.inverse()));
+                WinReason::Goal(spaces.unwrap())
+            } else if b.is_full() && rules.full_board_winner(b, to_move) == Some(piece) {
+                WinReason::GoalNeverMet
+            } else {
+                return Err(RecordError::ResultMismatch { line });
+            }
+        }
+        Some((_, text)) if text == "resignation" => WinReason::Resignation,
+        Some((_, text)) if text == "time" => WinReason::Timeout,
+        Some((line, text)) => {
+            let invalid = || RecordError::InvalidTermination {
+                line,
+                text: text.clone(),
+            };
+            let (coord, placed) = text
+                .strip_prefix("illegal")
+                .and_then(|c| parse_move(c).ok())
+                .ok_or_else(invalid)?;
+
+            // the move must still be illegal on the final board
+            let loser = piece.inverse();
+            let placed = placed.unwrap_or(loser);
+            if !rules.pieces_for(loser).contains(&placed) {
+                return Ok(Outcome::Win {
+                    piece,
+                    reason: WinReason::IllegalMove(IllegalMove {
+                        piece: loser,
+                        coord,
+                        error: GridError::PieceNotAllowed { piece: placed },
+                    }),
+                });
+            }
+            match b.clone().place(placed, coord.row, coord.col) {
+                Ok(()) => return Err(invalid()),
+                Err(error) => WinReason::IllegalMove(IllegalMove {
+                    piece: loser,
+                    coord,
+                    error,
+                }),
+            }
+        }
+    };
+    Ok(Outcome::Win { piece, reason })
+}
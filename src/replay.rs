@@ -0,0 +1,106 @@
+use crate::players::{AiSerial, MoveValue};
+use crate::record::GameRecord;
+use crate::space::{Coord, Piece};
+use crate::Board;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveEvaluation {
+    // value of the move played, for the player who played it
+    pub played: MoveValue,
+    // value of the best move available instead
+    pub best: MoveValue,
+}
+
+// Steps forward and back through a recorded game
+pub struct Replay {
+    record: GameRecord,
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(record: GameRecord) -> Self {
+        Self { record, position: 0 }
+    }
+
+    pub fn record(&self) -> &GameRecord {
+        &self.record
+    }
+
+    // number of moves made in the position being shown
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn board(&self) -> Board {
+        self.record.board_after(self.position)
+    }
+
+    // the move that led to the position being shown, with the piece placed, and who made it
+    pub fn last_move(&self) -> Option<(Piece, (Coord, Piece))> {
+        let i = self.position.checked_sub(1)?;
+        Some((self.record.piece_moving(i), self.record.moves[i]))
+    }
+
+    pub fn forward(&mut self) -> bool {
+        if self.position < self.record.moves.len() {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn back(&mut self) -> bool {
+        if self.position > 0 {
+            self.position -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn to_start(&mut self) {
+        self.position = 0;
+    }
+
+    pub fn to_end(&mut self) {
+        self.position = self.record.moves.len();
+    }
+
+    // Evaluates every move in the game with a full-depth solve
+    pub fn evaluate_moves(&self) -> Vec<MoveEvaluation> {
+        if self.record.rules.random_turn {
+            // the solvers behind these values assume the players take turns
+            return vec![];
+        }
+        let mut solver = AiSerial::new(
+            self.record.rows,
+            self.record.cols,
+            Piece::X,
+            usize::MAX,
+            self.record.rules,
+        );
+
+        (0..self.record.moves.len())
+            .map(|i| {
+                let mover = self.record.piece_moving(i);
+                let best = solver.evaluate(mover, &self.record.board_after(i));
+                let mut next = self.record.piece_moving(i + 1);
+                let mut after = self.record.board_after(i + 1);
+                // a swap is the opponent's answer to this move, so judge the move by the board
+                // before it, with the opponent to move
+                if self.record.swapped && self.record.swap_point() == Some(i + 1) {
+                    next = next.inverse();
+                    after.invert();
+                }
+                let mut played = solver.evaluate(next, &after);
+                // the opponent may swap sides right after this move, whether or not they did
+                if self.record.swap_point() == Some(i + 1) && self.record.rules.can_swap(&after) {
+                    played = played.with_swap();
+                }
+                let played = played.for_mover(next != mover);
+                MoveEvaluation { played, best }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,459 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::players::MoveValue;
+use crate::space::{Coord, Piece};
+use crate::win_condition::{Goal, WinCondition};
+use crate::{Board, ScrambledBoard};
+
+// The rules a game is played under, shared by the game engine and every solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rules {
+    pub goal: Goal,
+    // the player who first meets the goal loses instead of winning
+    pub misere: bool,
+    // None when both players try to meet the goal
+    pub maker_breaker: Option<MakerBreaker>,
+    // None when each player places only their own pieces
+    pub order_chaos: Option<OrderChaos>,
+    // how many pieces each player places per turn
+    pub bias: Bias,
+    // Pieces on the board before the first move, to give the weaker player a head start. They
+    // count toward the goal like any other, but not toward working out whose turn it is.
+    pub handicap: usize,
+    // Once the first player's first turn is over, the second player may swap sides instead of
+    // moving, taking over the first player's pieces and leaving the other player to move
+    pub pie: bool,
+    // A coin flip decides who moves each turn, so there is no order of turns for a bias, the pie
+    // rule or either player's role to go by. The usual solvers don't apply; see AiRandomTurn.
+    pub random_turn: bool,
+}
+
+// In a Maker-Breaker game only the Maker can meet the goal, and the Breaker wins by filling the
+// board without the Maker meeting it. In misère it's the other way around: the Maker has to avoid
+// meeting the goal and wins on a full board.
+//
+// Which piece is the Maker is worked out from the number of pieces on the board as if the game
+// started from an empty board, so that the solvers can tell even on the inverted boards they use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MakerBreaker {
+    MakerFirst,
+    BreakerFirst,
+}
+
+impl Display for MakerBreaker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MakerBreaker::MakerFirst => write!(f, "maker-first"),
+            MakerBreaker::BreakerFirst => write!(f, "breaker-first"),
+        }
+    }
+}
+
+// In an Order-and-Chaos game either player may place an X or an O on each move. Order wins as soon
+// as either piece meets the goal, and Chaos wins by filling the board without that happening. In
+// misère it's the other way around. Which player is Order is worked out the same way as which is
+// the Maker, and since the pieces belong to neither player, inverting a board changes nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderChaos {
+    OrderFirst,
+    ChaosFirst,
+}
+
+impl Display for OrderChaos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderChaos::OrderFirst => write!(f, "order-first"),
+            OrderChaos::ChaosFirst => write!(f, "chaos-first"),
+        }
+    }
+}
+
+// The first player places `first` pieces on each of their turns and the second player `second`,
+// e.g. 1:2 lets the second player place two pieces for every one of the first player's. The last
+// turn is cut short if the board fills up. Each placement is a move of its own as far as the
+// solvers are concerned, so positions partway through a turn are standardized like any other.
+//
+// Since the order of turns is fixed, the number of pieces on the board apart from any handicap
+// says whose turn it is, which is how the solvers tell on the inverted boards they use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bias {
+    pub first: usize,
+    pub second: usize,
+}
+
+impl Default for Bias {
+    fn default() -> Self {
+        Self {
+            first: 1,
+            second: 1,
+        }
+    }
+}
+
+impl Display for Bias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.first, self.second)
+    }
+}
+
+// e.g. "1:2", with at least one piece per turn for both players
+impl FromStr for Bias {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, second) = s.split_once(':').ok_or(())?;
+        let first = first.parse().map_err(|_| ())?;
+        let second = second.parse().map_err(|_| ())?;
+        if first == 0 || second == 0 {
+            return Err(());
+        }
+        Ok(Self { first, second })
+    }
+}
+
+impl Bias {
+    // whether the first player places the next piece once `placed` pieces have been placed
+    pub fn first_places(&self, placed: usize) -> bool {
+        placed % (self.first + self.second) < self.first
+    }
+
+    // how many of the first `placed` pieces the first player placed
+    pub fn placed_by_first(&self, placed: usize) -> usize {
+        let turns = placed / (self.first + self.second);
+        let rest = placed % (self.first + self.second);
+        turns * self.first + rest.min(self.first)
+    }
+}
+
+impl Rules {
+    pub fn misere() -> Self {
+        Self {
+            misere: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_goal(goal: Goal) -> Self {
+        Self {
+            goal,
+            ..Self::default()
+        }
+    }
+
+    pub fn maker_breaker(maker_breaker: MakerBreaker) -> Self {
+        Self {
+            maker_breaker: Some(maker_breaker),
+            ..Self::default()
+        }
+    }
+
+    pub fn order_chaos(order_chaos: OrderChaos) -> Self {
+        Self {
+            order_chaos: Some(order_chaos),
+            ..Self::default()
+        }
+    }
+
+    pub fn random_turn() -> Self {
+        Self {
+            random_turn: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn biased(bias: Bias) -> Self {
+        Self {
+            bias,
+            ..Self::default()
+        }
+    }
+
+    // pieces placed on b since the first move, leaving out the handicap
+    pub fn placed(&self, b: &Board) -> usize {
+        (b.count(Piece::X) + b.count(Piece::O)).saturating_sub(self.handicap)
+    }
+
+    // the piece to place the next piece once `placed` pieces have been placed, given the first player's
+    pub fn piece_placing(&self, placed: usize, first: Piece) -> Piece {
+        if self.bias.first_places(placed) {
+            first
+        } else {
+            first.inverse()
+        }
+    }
+
+    // The piece that moved first, as if the game started from an empty board, given the piece to
+    // place the next piece on b
+    pub fn first_player(&self, b: &Board, to_move: Piece) -> Piece {
+        self.piece_placing(self.placed(b), to_move)
+    }
+
+    // the piece that placed the last piece on b, given the piece to place the next one
+    pub fn last_mover(&self, b: &Board, to_move: Piece) -> Piece {
+        match self.placed(b).checked_sub(1) {
+            Some(last) => self.piece_placing(last, self.first_player(b, to_move)),
+            None => to_move.inverse(),
+        }
+    }
+
+    // the piece to place the next piece after mover placed the last one on b
+    pub fn next_to_move(&self, b: &Board, mover: Piece) -> Piece {
+        match self.placed(b).checked_sub(1) {
+            Some(last) => self.piece_placing(self.placed(b), self.piece_placing(last, mover)),
+            None => mover.inverse(),
+        }
+    }
+
+    // Whether the player to move on b would be able to swap sides under the pie rule, assuming
+    // they haven't already had the chance. The first player's pieces become theirs, so swapping
+    // leaves the same board for the other player as moving leaves for the swapper: it is worth the
+    // reverse of what the board is worth to play on.
+    pub fn can_swap(&self, b: &Board) -> bool {
+        self.pie
+            && self.placed(b) == self.bias.first
+            && !b.is_full()
+            && !self.has_win(b, Piece::X)
+            && !self.has_win(b, Piece::O)
+    }
+
+    // The Maker's piece on b with to_move to play, if this is a Maker-Breaker game
+    pub fn maker(&self, b: &Board, to_move: Piece) -> Option<Piece> {
+        let first = self.first_player(b, to_move);
+        match self.maker_breaker? {
+            MakerBreaker::MakerFirst => Some(first),
+            MakerBreaker::BreakerFirst => Some(first.inverse()),
+        }
+    }
+
+    // Order's piece on b with to_move to play, if this is an Order-and-Chaos game
+    pub fn order(&self, b: &Board, to_move: Piece) -> Option<Piece> {
+        let first = self.first_player(b, to_move);
+        match self.order_chaos? {
+            OrderChaos::OrderFirst => Some(first),
+            OrderChaos::ChaosFirst => Some(first.inverse()),
+        }
+    }
+
+    // the pieces mover may place: only their own, unless this is an Order-and-Chaos game
+    pub fn pieces_for(&self, mover: Piece) -> Vec<Piece> {
+        if self.order_chaos.is_some() {
+            vec![Piece::X, Piece::O]
+        } else {
+            vec![mover]
+        }
+    }
+
+    // the spaces with which piece has met the goal on b, if it has
+    pub fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>> {
+        self.goal.winning_spaces(b, piece)
+    }
+
+    pub fn has_win(&self, b: &Board, piece: Piece) -> bool {
+        self.goal.has_win(b, piece)
+    }
+
+    // puts b into the form the solvers key positions by, which depends on the goal's symmetries
+    pub fn standardize(&self, b: &mut ScrambledBoard) {
+        self.goal.standardize(b);
+    }
+
+    pub fn standardized(&self, mut b: ScrambledBoard) -> ScrambledBoard {
+        self.standardize(&mut b);
+        b
+    }
+
+    // The winner of the game, if mover's last move on b ended it with a winner
+    pub fn winner(&self, b: &Board, mover: Piece) -> Option<Piece> {
+        if let Some(order) = self.order(b, self.next_to_move(b, mover)) {
+            // either piece meeting the goal counts for Order, whoever placed it
+            if !self.has_win(b, Piece::X) && !self.has_win(b, Piece::O) {
+                None
+            } else if self.misere {
+                Some(order.inverse())
+            } else {
+                Some(order)
+            }
+        } else if self.maker(b, self.next_to_move(b, mover)).is_some_and(|maker| maker != mover) {
+            // the Breaker's pieces never count
+            None
+        } else if !self.has_win(b, mover) {
+            None
+        } else if self.misere {
+            Some(mover.inverse())
+        } else {
+            Some(mover)
+        }
+    }
+
+    // The winner once b is full without the goal met, with to_move to play if there were a move
+    // left. None for a tie, as there is unless this is a Maker-Breaker or Order-and-Chaos game.
+    pub fn full_board_winner(&self, b: &Board, to_move: Piece) -> Option<Piece> {
+        // Order is the Maker as far as a full board goes
+        let maker = self.maker(b, to_move).or_else(|| self.order(b, to_move))?;
+        if self.misere {
+            Some(maker)
+        } else {
+            Some(maker.inverse())
+        }
+    }
+
+    // The value of b for to_move if the game is already over there, as the solvers see it
+    pub fn terminal_value(&self, b: &Board, to_move: Piece) -> Option<MoveValue> {
+        let winner = match self.winner(b, self.last_mover(b, to_move)) {
+            Some(winner) => winner,
+            None if b.is_full() => match self.full_board_winner(b, to_move) {
+                Some(winner) => winner,
+                None => return Some(MoveValue::Tie(0)),
+            },
+            None => return None,
+        };
+        if winner == to_move {
+            Some(MoveValue::Win(0))
+        } else {
+            Some(MoveValue::Lose(0))
+        }
+    }
+
+    // added to strategy file names so strategies for different rules are kept apart
+    pub fn file_suffix(&self) -> String {
+        let misere = if self.misere { "-misere" } else { "" };
+        let maker_breaker = match self.maker_breaker {
+            Some(MakerBreaker::MakerFirst) => "-makerfirst",
+            Some(MakerBreaker::BreakerFirst) => "-breakerfirst",
+            None => "",
+        };
+        let order_chaos = match self.order_chaos {
+            Some(OrderChaos::OrderFirst) => "-orderfirst",
+            Some(OrderChaos::ChaosFirst) => "-chaosfirst",
+            None => "",
+        };
+        let bias = if self.bias == Bias::default() {
+            String::new()
+        } else {
+            format!("-bias{}to{}", self.bias.first, self.bias.second)
+        };
+        let handicap = if self.handicap == 0 {
+            String::new()
+        } else {
+            format!("-handicap{}", self.handicap)
+        };
+        let pie = if self.pie { "-pie" } else { "" };
+        let random_turn = if self.random_turn { "-randomturn" } else { "" };
+        format!(
+            "{}{}{}{}{}{}{}{}",
+            self.goal.file_suffix(),
+            bias,
+            handicap,
+            maker_breaker,
+            order_chaos,
+            pie,
+            random_turn,
+            misere
+        )
+    }
+}
+
+// "standard" for the default rules, otherwise whatever differs from them, e.g. "misere 1:2 3-in-a-row"
+impl Display for Rules {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.misere {
+            parts.push("misere".to_string());
+        }
+        if self.pie {
+            parts.push("pie".to_string());
+        }
+        if self.random_turn {
+            parts.push("random-turn".to_string());
+        }
+        if let Some(maker_breaker) = self.maker_breaker {
+            parts.push(maker_breaker.to_string());
+        }
+        if let Some(order_chaos) = self.order_chaos {
+            parts.push(order_chaos.to_string());
+        }
+        if self.bias != Bias::default() {
+            parts.push(self.bias.to_string());
+        }
+        if self.handicap != 0 {
+            parts.push(format!("{}-handicap", self.handicap));
+        }
+        if self.goal != Goal::default() {
+            parts.push(self.goal.to_string());
+        }
+
+        if parts.is_empty() {
+            write!(f, "standard")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRulesError {
+    pub input: String,
+}
+
+impl Display for ParseRulesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown rules \"{}\", expected \"standard\" or any of \"misere\", \"pie\", \"random-turn\", \"maker-first\", \"breaker-first\", \"order-first\" or \"chaos-first\", a bias such as \"1:2\", a handicap such as \"2-handicap\" and a goal such as \"transversal\", \"3-in-a-row\" or \"3-partial-transversal\"",
+            self.input
+        )
+    }
+}
+
+impl FromStr for Rules {
+    type Err = ParseRulesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Self::default();
+        for word in s.to_lowercase().split_whitespace() {
+            match word {
+                "standard" | "normal" => (),
+                "misere" | "misère" => rules.misere = true,
+                "pie" => rules.pie = true,
+                "random-turn" => rules.random_turn = true,
+                "maker-first" => rules.maker_breaker = Some(MakerBreaker::MakerFirst),
+                "breaker-first" => rules.maker_breaker = Some(MakerBreaker::BreakerFirst),
+                "order-first" => rules.order_chaos = Some(OrderChaos::OrderFirst),
+                "chaos-first" => rules.order_chaos = Some(OrderChaos::ChaosFirst),
+                _ if word.ends_with("-handicap") => {
+                    rules.handicap = word
+                        .trim_end_matches("-handicap")
+                        .parse()
+                        .map_err(|_| ParseRulesError {
+                            input: s.to_string(),
+                        })?
+                }
+                _ if word.contains(':') => {
+                    rules.bias = word.parse().map_err(|_| ParseRulesError {
+                        input: s.to_string(),
+                    })?
+                }
+                _ => {
+                    rules.goal = word.parse().map_err(|_| ParseRulesError {
+                        input: s.to_string(),
+                    })?
+                }
+            }
+        }
+        let turn_order = rules.maker_breaker.is_some()
+            || rules.order_chaos.is_some()
+            || rules.pie
+            || rules.bias != Bias::default();
+        if (rules.maker_breaker.is_some() && rules.order_chaos.is_some())
+            || (rules.random_turn && turn_order)
+        {
+            // the two kinds of game give the players different roles, and random turns leave
+            // nothing for roles, a bias or the pie rule to go by
+            return Err(ParseRulesError {
+                input: s.to_string(),
+            });
+        }
+        Ok(rules)
+    }
+}
@@ -1,36 +1,55 @@
 use std::cmp::Ordering;
+use std::fmt::Display;
+use std::str::FromStr;
 
-use crate::board::GridError;
+use crate::board::{GridError, ParseBoardError};
 use crate::Board;
 use crate::space::{Coord, Space, Piece};
 
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ScrambledBoard {
-    pub size: usize,
+    pub rows: usize,
+    pub cols: usize,
     grid: Vec<Space>,
 }
 
 impl From<Board> for ScrambledBoard {
     fn from(b: Board) -> Self {
-        let size = b.size;
+        let (rows, cols) = (b.rows, b.cols);
         let mut grid = Vec::new();
         for (i, &piece) in b.flat().enumerate() {
             grid.push(Space {
                 piece,
-                row: i / size,
-                col: i % size
+                row: i / cols,
+                col: i % cols
             })
         }
         
 
-        Self { size, grid }
+        Self { rows, cols, grid }
+    }
+}
+
+// Written in the same notation as Board, showing the pieces where they currently are. Parsing
+// gives a board that hasn't been scrambled yet.
+impl Display for ScrambledBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Board::from(self))
+    }
+}
+
+impl FromStr for ScrambledBoard {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s.parse::<Board>()?))
     }
 }
 
 impl ScrambledBoard {
     pub fn to_original_board(&self) -> Board {
-        let mut b = Board::new(self.size);
+        let mut b = Board::new(self.rows, self.cols);
         for space in self.spaces() {
             b.place(space.piece, space.row, space.col).unwrap();
         }
@@ -47,35 +66,33 @@ impl ScrambledBoard {
 
     pub fn space_at(&self, coordinate: Coord) -> Result<Space, GridError> {
         let (row, col) = (coordinate.row, coordinate.col);
-        if row >= self.size {
+        if row >= self.rows {
             return Err(GridError::RowIndexOutOfBounds {
                 idx_found: row,
-                board_size: self.size,
+                rows: self.rows,
             });
         }
-        if col >= self.size {
+        if col >= self.cols {
             return Err(GridError::ColIndexOutOfBounds {
                 idx_found: col,
-                board_size: self.size,
+                cols: self.cols,
             });
         }
-        Ok(self.grid[row*self.size + col])
+        Ok(self.grid[row*self.cols + col])
     }
 
     pub fn piece_at(&self, coordinate: Coord) -> Result<Piece, GridError> {
         Ok(self.space_at(coordinate)?.piece)
     }
 
+    // swaps rows for columns, so a board with more columns than rows ends up with more rows
     pub fn transpose(&mut self) {
-        let size = self.size;
-        for row in 0..size {
-            // for each index in row, starting from (but not including) the main diagonal
-            for i in (row*(size+1) + 1)..(row+1)*size {
-                let j = (i % size) * size + i / size;
-                self.grid.swap(i, j)
-
-            } 
-        }
+        let grid = (0..self.cols)
+            .flat_map(|col| (0..self.rows).map(move |row| (row, col)))
+            .map(|(row, col)| self.grid[row*self.cols + col])
+            .collect();
+        self.grid = grid;
+        std::mem::swap(&mut self.rows, &mut self.cols);
     }
 
     fn transposed(&self) -> Self {
@@ -84,6 +101,21 @@ impl ScrambledBoard {
         new_board
     }
 
+    // swaps the top and bottom of the board
+    pub fn flip_rows(&mut self) {
+        self.grid = self.grid.chunks(self.cols).rev().flatten().copied().collect();
+    }
+
+    // swaps the left and right of the board
+    pub fn flip_cols(&mut self) {
+        self.grid = self
+            .grid
+            .chunks(self.cols)
+            .flat_map(|row| row.iter().rev())
+            .copied()
+            .collect();
+    }
+
     pub fn invert(&mut self) {
         for space in self.spaces_mut() {
             space.piece = space.piece.inverse();
@@ -96,18 +128,44 @@ impl ScrambledBoard {
         new_board
     }
 
+    // Transposing is only a symmetry of square boards, so a rectangular board keeps its shape
     pub fn standardize(&mut self) {
-        let mut rows: Vec<&[Space]> = self.grid.chunks(self.size).collect();
+        let mut rows: Vec<&[Space]> = self.grid.chunks(self.cols).collect();
         rows.sort_unstable_by(row_cmp);
         self.grid = rows.into_iter().flatten().map(|s| *s).collect();
 
         self.transpose();
         
-        let mut rows: Vec<&[Space]> = self.grid.chunks(self.size).collect();
+        let mut rows: Vec<&[Space]> = self.grid.chunks(self.cols).collect();
         rows.sort_unstable_by(row_cmp);
         self.grid = rows.into_iter().flatten().map(|s| *s).collect();
         
-        *self = self.clone().min(self.transposed());
+        if self.rows == self.cols {
+            *self = self.clone().min(self.transposed());
+        } else {
+            self.transpose();
+        }
+    }
+
+    // Like standardize, but only using rotations and reflections, for win conditions that depend
+    // on which spaces are next to each other. Compares pieces alone so that equivalent boards
+    // always end up with the same pieces in the same places. A rectangular board only has its
+    // reflections, without the rotations a quarter turn that would change its shape.
+    pub fn standardize_dihedral(&mut self) {
+        let transposes = if self.rows == self.cols { 2 } else { 1 };
+        let mut candidate = self.clone();
+        for _ in 0..transposes {
+            for _ in 0..2 {
+                for _ in 0..2 {
+                    if pieces(&candidate).lt(pieces(self)) {
+                        *self = candidate.clone();
+                    }
+                    candidate.flip_cols();
+                }
+                candidate.flip_rows();
+            }
+            candidate.transpose();
+        }
     }
 
     pub fn standardized(&self) -> Self {
@@ -138,12 +196,24 @@ fn row_cmp(left: &&[Space], right: &&[Space]) -> Ordering {
     let left_count = count(left, Piece::X);
     let right_count = count(right, Piece::X);
     match left_count.cmp(&right_count) {
-        // if X's equal and nonzero, use derived comparison
+        // if X's equal, move to next step of comparison
+        Ordering::Equal => (),
+        o => return o,
+    };
+
+    let left_count = count(left, Piece::Blocked);
+    let right_count = count(right, Piece::Blocked);
+    match left_count.cmp(&right_count) {
+        // if blocked spaces equal too, use derived comparison
         Ordering::Equal => left.cmp(right),
         o => o,
     }
 }
 
+fn pieces(b: &ScrambledBoard) -> impl Iterator<Item = Piece> + '_ {
+    b.spaces().map(|space| space.piece)
+}
+
 fn count(row: &[Space], p: Piece) -> usize {
     row.iter().filter(|&space| space.piece == p).count()
 }
@@ -1,4 +1,5 @@
 use std::fmt::{Debug, Display};
+use std::str::FromStr;
 use colored::{Colorize, ColoredString};
 use serde::{Serialize, Deserialize};
 
@@ -6,7 +7,9 @@ use serde::{Serialize, Deserialize};
 pub enum Piece {
     X,
     O,
-    Empty
+    Empty,
+    // a space neither player can use, which never counts toward a goal
+    Blocked
 }
 
 impl Piece {
@@ -14,7 +17,8 @@ impl Piece {
         match self {
             Self::X => Self::O,
             Self::O => Self::X,
-            Self::Empty => Self::Empty
+            Self::Empty => Self::Empty,
+            Self::Blocked => Self::Blocked
         }
     }
     
@@ -22,7 +26,8 @@ impl Piece {
         match self {
             Self::X => s.blue(),
             Self::O => s.green(),
-            Self::Empty => s.normal()
+            Self::Empty => s.normal(),
+            Self::Blocked => s.red()
         }
     }
 }
@@ -32,7 +37,8 @@ impl Display for Piece {
         let piece_as_str = match self {
             Self::X => Self::X.colorize("X"),
             Self::O => Self::O.colorize("O"),
-            Self::Empty => Self::Empty.colorize("·")
+            Self::Empty => Self::Empty.colorize("·"),
+            Self::Blocked => Self::Blocked.colorize("#")
         };
         write!(f, "{}", piece_as_str)
     }
@@ -46,6 +52,116 @@ pub struct Coord {
 }
 
 
+impl Coord {
+    // A to Z, then AA to AZ, BA and so on, like spreadsheet columns
+    pub fn col_label(col: usize) -> String {
+        let mut label = Vec::new();
+        let mut n = col + 1;
+        while n > 0 {
+            n -= 1;
+            label.push(b'A' + (n % 26) as u8);
+            n /= 26;
+        }
+        label.reverse();
+        String::from_utf8(label).unwrap()
+    }
+
+    // inverse of col_label, ignoring case. None if s isn't all letters or is too long to fit.
+    pub fn col_from_label(s: &str) -> Option<usize> {
+        if s.is_empty() {
+            return None;
+        }
+        let mut n: usize = 0;
+        for c in s.chars() {
+            if !c.is_ascii_alphabetic() {
+                return None;
+            }
+            let digit = (c.to_ascii_uppercase() as u8 - b'A') as usize + 1;
+            n = n.checked_mul(26)?.checked_add(digit)?;
+        }
+        Some(n - 1)
+    }
+}
+
+// A1 notation: column letters, then row number starting from 1
+impl Display for Coord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", Self::col_label(self.col), self.row + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCoordError {
+    pub input: String,
+}
+
+impl Display for ParseCoordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid space \"{}\", expected the form \"A1\" or \"r1c1\"",
+            self.input
+        )
+    }
+}
+
+// a row or column number starting from 1
+fn parse_index(s: &str) -> Option<usize> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse::<usize>().ok()?.checked_sub(1)
+}
+
+// Accepts A1 notation with any number of letters and digits, e.g. "B3" or "AA12", or row and
+// column numbers, e.g. "r3c2" for row 3, column 2. Case is ignored.
+impl FromStr for Coord {
+    type Err = ParseCoordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseCoordError { input: s.to_string() };
+        let lower = s.trim().to_lowercase();
+
+        // r3c2
+        if let Some((row_str, col_str)) = lower.strip_prefix('r').and_then(|rest| rest.split_once('c')) {
+            if let (Some(row), Some(col)) = (parse_index(row_str), parse_index(col_str)) {
+                return Ok(Coord { row, col });
+            }
+        }
+
+        // B3
+        let split = lower
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .ok_or_else(err)?;
+        let (col_str, row_str) = lower.split_at(split);
+        let col = Coord::col_from_label(col_str).ok_or_else(err)?;
+        let row = parse_index(row_str).ok_or_else(err)?;
+        Ok(Coord { row, col })
+    }
+}
+
+// A move as written in records and typed in: a space, then "=" and the piece placed there when
+// the rules let either be placed, e.g. "B2=O". The piece is None if left off, for the mover's own.
+pub fn parse_move(s: &str) -> Result<(Coord, Option<Piece>), ParseCoordError> {
+    let Some((coord, piece)) = s.trim().split_once('=') else {
+        return Ok((s.parse()?, None));
+    };
+    let piece = match piece.trim().to_lowercase().as_str() {
+        "x" => Piece::X,
+        "o" => Piece::O,
+        _ => return Err(ParseCoordError { input: s.to_string() }),
+    };
+    Ok((coord.parse()?, Some(piece)))
+}
+
+// the move written the way parse_move reads it, with the piece placed
+pub fn format_move(coord: Coord, piece: Piece) -> String {
+    // not the piece's own Display, which is colored
+    let piece = if piece == Piece::X { "X" } else { "O" };
+    format!("{}={}", coord, piece)
+}
+
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Space {
     pub piece: Piece,
@@ -0,0 +1,508 @@
+use ciborium::{de, ser};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::path::Path;
+
+use crate::players::{available_moves, place_for_key, LazyMoveAnalysis, MoveAnalysis, MoveValue};
+use crate::rules::{Bias, MakerBreaker, OrderChaos, Rules};
+use crate::win_condition::Goal;
+use crate::space::{Coord, Piece};
+use crate::{format_dimensions, parse_dimensions, Board, ScrambledBoard};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Solver {
+    Serial { depth: usize },
+    Lazy,
+    Parallel,
+    GroundUp,
+}
+
+impl Solver {
+    // whether the solver trusts every stored evaluation, so depth-limited Unknown entries must not be given to it
+    pub fn is_complete(&self) -> bool {
+        !matches!(self, Solver::Serial { .. })
+    }
+}
+
+#[derive(Debug)]
+pub enum StrategyError {
+    UnrecognizedPath(String),
+    Io(std::io::Error),
+    Cbor(String),
+    // rows and columns of the boards
+    SizeMismatch { expected: (usize, usize), found: (usize, usize) },
+    RulesMismatch { expected: Rules, found: Rules },
+}
+
+impl Display for StrategyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StrategyError::UnrecognizedPath(path) => {
+                write!(
+                    f,
+                    "Could not tell solver, size and piece from file name {}",
+                    path
+                )
+            }
+            StrategyError::Io(e) => write!(f, "{}", e),
+            StrategyError::Cbor(e) => write!(f, "Invalid strategy data: {}", e),
+            StrategyError::SizeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Expected a strategy for board size {}, but found size {}",
+                    format_dimensions(expected.0, expected.1),
+                    format_dimensions(found.0, found.1)
+                )
+            }
+            StrategyError::RulesMismatch { expected, found } => {
+                write!(
+                    f,
+                    "Expected a strategy for {} rules, but found {} rules",
+                    expected, found
+                )
+            }
+        }
+    }
+}
+
+impl From<std::io::Error> for StrategyError {
+    fn from(e: std::io::Error) -> Self {
+        StrategyError::Io(e)
+    }
+}
+
+// The solver, board size, piece and rules of a strategy file, as encoded in each solver's cbor_path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrategyFile {
+    pub solver: Solver,
+    pub rows: usize,
+    pub cols: usize,
+    pub piece: Piece,
+    pub rules: Rules,
+}
+
+impl StrategyFile {
+    pub fn from_path(path: &str) -> Result<Self, StrategyError> {
+        let unrecognized = || StrategyError::UnrecognizedPath(path.to_string());
+        let stem = Path::new(path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(unrecognized)?;
+
+        let mut dimensions = None;
+        let mut piece = None;
+        let mut depth = None;
+        let mut rules = Rules::default();
+        for token in stem.split('-') {
+            if token == "misere" {
+                rules.misere = true;
+                continue;
+            }
+            if token == "pie" {
+                rules.pie = true;
+                continue;
+            }
+            if token == "randomturn" {
+                rules.random_turn = true;
+                continue;
+            }
+            if token == "makerfirst" {
+                rules.maker_breaker = Some(MakerBreaker::MakerFirst);
+                continue;
+            }
+            if token == "breakerfirst" {
+                rules.maker_breaker = Some(MakerBreaker::BreakerFirst);
+                continue;
+            }
+            if token == "orderfirst" {
+                rules.order_chaos = Some(OrderChaos::OrderFirst);
+                continue;
+            }
+            if token == "chaosfirst" {
+                rules.order_chaos = Some(OrderChaos::ChaosFirst);
+                continue;
+            }
+            if let Some(Ok(bias)) = token
+                .strip_prefix("bias")
+                .map(|b| b.replacen("to", ":", 1).parse::<Bias>())
+            {
+                rules.bias = bias;
+                continue;
+            }
+            if let Some(Ok(handicap)) = token.strip_prefix("handicap").map(|h| h.parse()) {
+                rules.handicap = handicap;
+                continue;
+            }
+            if let Some(Ok(length)) = token.strip_prefix("row").map(|l| l.parse()) {
+                rules.goal = Goal::InARow(length);
+                continue;
+            }
+            if let Some(Ok(pieces)) = token.strip_prefix("partial").map(|p| p.parse()) {
+                rules.goal = Goal::PartialTransversal(pieces);
+                continue;
+            }
+
+            let mut chars = token.chars();
+            match (chars.next(), chars.as_str()) {
+                (Some('s'), rest) => dimensions = dimensions.or(parse_dimensions(rest)),
+                (Some('d'), rest) => depth = depth.or(rest.parse().ok()),
+                (Some('p'), "X") => piece = Some(Piece::X),
+                (Some('p'), "O") => piece = Some(Piece::O),
+                _ => (),
+            }
+        }
+
+        let solver = if stem.starts_with("serial-") {
+            Solver::Serial {
+                depth: depth.ok_or_else(unrecognized)?,
+            }
+        } else if stem.starts_with("lazy-") {
+            Solver::Lazy
+        } else if stem.starts_with("parallel-") {
+            Solver::Parallel
+        } else if stem.starts_with("ground-up-") {
+            Solver::GroundUp
+        } else {
+            return Err(unrecognized());
+        };
+
+        let (rows, cols) = dimensions.ok_or_else(unrecognized)?;
+        Ok(Self {
+            solver,
+            rows,
+            cols,
+            piece: piece.ok_or_else(unrecognized)?,
+            rules,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub board: Board,
+    pub kept: MoveValue,
+    pub discarded: MoveValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum Inconsistency {
+    // stored evaluation differs from the one recomputed from the position's children or terminal state
+    Evaluation {
+        board: Board,
+        stored: MoveValue,
+        recomputed: MoveValue,
+    },
+    // a stored move option does not lead to the stored evaluation
+    MoveOption {
+        board: Board,
+        option: (Coord, Piece),
+        leads_to: MoveValue,
+    },
+    // a position reachable in one move from a stored position has no entry of its own
+    Missing { board: Board, parent: Board },
+}
+
+impl Display for Inconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Inconsistency::Evaluation {
+                board,
+                stored,
+                recomputed,
+            } => {
+                write!(
+                    f,
+                    "Stored {:?} but recomputed {:?} for\n{}",
+                    stored,
+                    recomputed,
+                    board.pretty()
+                )
+            }
+            Inconsistency::MoveOption {
+                board,
+                option,
+                leads_to,
+            } => {
+                write!(
+                    f,
+                    "Move option {} at ({}, {}) leads to {:?} instead of the stored evaluation for\n{}",
+                    option.1,
+                    option.0.row,
+                    option.0.col,
+                    leads_to,
+                    board.pretty()
+                )
+            }
+            Inconsistency::Missing { board, parent } => {
+                write!(
+                    f,
+                    "No entry for\n{}\nwhich follows from\n{}",
+                    board.pretty(),
+                    parent.pretty()
+                )
+            }
+        }
+    }
+}
+
+// A strategy from any solver, held in the common MoveAnalysis format.
+// Keys are standardized boards with piece to move, as in every solver's known_boards.
+pub struct Strategy {
+    pub rows: usize,
+    pub cols: usize,
+    pub piece: Piece,
+    pub rules: Rules,
+    pub known_boards: HashMap<Board, MoveAnalysis>,
+}
+
+impl Strategy {
+    pub fn new(rows: usize, cols: usize, piece: Piece, rules: Rules) -> Self {
+        Self {
+            rows,
+            cols,
+            piece,
+            rules,
+            known_boards: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, StrategyError> {
+        let file = StrategyFile::from_path(path)?;
+        let f = File::open(path)?;
+        let mut strategy = Self::new(file.rows, file.cols, file.piece, file.rules);
+
+        match file.solver {
+            Solver::Lazy => {
+                let known_boards: HashMap<Board, LazyMoveAnalysis> =
+                    de::from_reader(f).map_err(|e| StrategyError::Cbor(e.to_string()))?;
+                for (b, analysis) in known_boards {
+                    let analysis = MoveAnalysis {
+                        evaluation: analysis.evaluation,
+                        move_options: analysis.move_option.into_iter().collect(),
+                        depth_used: file.rows * file.cols, // lazy solves are always complete
+                    };
+                    strategy.known_boards.insert(b, analysis);
+                }
+            }
+            _ => {
+                strategy.known_boards =
+                    de::from_reader(f).map_err(|e| StrategyError::Cbor(e.to_string()))?;
+            }
+        }
+        for analysis in strategy.known_boards.values_mut() {
+            analysis.fill_in_pieces(file.piece);
+        }
+
+        Ok(strategy)
+    }
+
+    // saves in the format of whichever solver the file name at path belongs to
+    pub fn save(&self, path: &str) -> Result<(), StrategyError> {
+        let file = StrategyFile::from_path(path)?;
+        if (file.rows, file.cols) != (self.rows, self.cols) {
+            return Err(StrategyError::SizeMismatch {
+                expected: (file.rows, file.cols),
+                found: (self.rows, self.cols),
+            });
+        }
+        if file.rules != self.rules {
+            return Err(StrategyError::RulesMismatch {
+                expected: file.rules,
+                found: self.rules,
+            });
+        }
+
+        let mut converted = self.clone_known_boards(file.piece);
+        if file.solver.is_complete() {
+            converted.retain(|_, analysis| analysis.evaluation.is_known());
+        }
+
+        let buffer = File::create(path)?;
+        match file.solver {
+            Solver::Lazy => {
+                let known_boards: HashMap<Board, LazyMoveAnalysis> = converted
+                    .into_iter()
+                    .map(|(b, analysis)| (b, LazyMoveAnalysis::from(analysis)))
+                    .collect();
+                ser::into_writer(&known_boards, buffer)
+            }
+            _ => ser::into_writer(&converted, buffer),
+        }
+        .map_err(|e| StrategyError::Cbor(e.to_string()))
+    }
+
+    // re-keys every entry so that piece is the one to move
+    pub fn set_piece(&mut self, piece: Piece) {
+        self.known_boards = self.clone_known_boards(piece);
+        self.piece = piece;
+    }
+
+    fn clone_known_boards(&self, piece: Piece) -> HashMap<Board, MoveAnalysis> {
+        if piece == self.piece {
+            return self.known_boards.clone();
+        }
+
+        self.known_boards
+            .iter()
+            .map(|(b, analysis)| {
+                // inverting a standardized board does not leave it standardized, so standardize
+                // again and follow each move option to wherever it ended up
+                let scrambled = self.rules.standardized(ScrambledBoard::from(b.inverse()));
+                let move_options = analysis
+                    .move_options
+                    .iter()
+                    .map(|&(c, p)| (standardized_coord(&scrambled, c), p.inverse()))
+                    .collect();
+
+                let analysis = MoveAnalysis {
+                    evaluation: analysis.evaluation.clone(),
+                    move_options,
+                    depth_used: analysis.depth_used,
+                };
+                (Board::from(scrambled), analysis)
+            })
+            .collect()
+    }
+
+    // Checks every entry against the terminal state of its board or the stored values of its children.
+    // Entries with an Unknown evaluation are depth-limited bounds and are not checked. Unless strict,
+    // a winning evaluation only needs to match the chosen moves rather than the quickest win, and
+    // only the children the chosen moves lead to need to be stored, since AiLazy and AiParallel stop
+    // searching at the first win they find.
+    pub fn verify(&self, strict: bool) -> Vec<Inconsistency> {
+        let mut inconsistencies = Vec::new();
+
+        for (b, analysis) in &self.known_boards {
+            if !analysis.evaluation.is_known() {
+                continue;
+            }
+
+            if let Some(recomputed) = self.rules.terminal_value(b, self.piece) {
+                if analysis.evaluation != recomputed {
+                    inconsistencies.push(Inconsistency::Evaluation {
+                        board: b.clone(),
+                        stored: analysis.evaluation.clone(),
+                        recomputed,
+                    });
+                }
+                continue;
+            }
+
+            let moves = available_moves(b, self.piece, &self.rules);
+            let mut children = Vec::new();
+            for &m in &moves {
+                let (child, turn_passed) = place_for_key(b, self.piece, m, &self.rules);
+                let child = Board::from(self.rules.standardized(ScrambledBoard::from(child)));
+
+                match self.known_boards.get(&child) {
+                    Some(child_analysis) => {
+                        let mut evaluation = child_analysis.evaluation.clone();
+                        if self.rules.can_swap(&child) {
+                            evaluation = evaluation.with_swap();
+                        }
+                        children.push((m, evaluation.for_mover(turn_passed)))
+                    }
+                    None if strict || analysis.move_options.contains(&m) => {
+                        inconsistencies.push(Inconsistency::Missing {
+                            board: child,
+                            parent: b.clone(),
+                        })
+                    }
+                    None => (),
+                }
+            }
+
+            for &option in &analysis.move_options {
+                if let Some((_, leads_to)) = children.iter().find(|(m, _)| *m == option) {
+                    if *leads_to != analysis.evaluation {
+                        inconsistencies.push(Inconsistency::MoveOption {
+                            board: b.clone(),
+                            option,
+                            leads_to: leads_to.clone(),
+                        });
+                    }
+                }
+            }
+
+            if children.len() < moves.len() {
+                // can't recompute a value with children missing
+                continue;
+            }
+            let recomputed = children.into_iter().map(|(_, v)| v).max().unwrap();
+            let matches = if strict || !matches!(recomputed, MoveValue::Win(_)) {
+                analysis.evaluation == recomputed
+            } else {
+                !analysis.evaluation.conflicts_with(&recomputed)
+            };
+            if !matches {
+                inconsistencies.push(Inconsistency::Evaluation {
+                    board: b.clone(),
+                    stored: analysis.evaluation.clone(),
+                    recomputed,
+                });
+            }
+        }
+
+        inconsistencies
+    }
+
+    // Adds every entry of other to self, keeping whichever analysis of a shared position is better
+    // informed. Returns the positions whose known evaluations disagree; for those, self's is kept.
+    pub fn merge(&mut self, mut other: Strategy) -> Result<Vec<Conflict>, StrategyError> {
+        if (other.rows, other.cols) != (self.rows, self.cols) {
+            return Err(StrategyError::SizeMismatch {
+                expected: (self.rows, self.cols),
+                found: (other.rows, other.cols),
+            });
+        }
+        if other.rules != self.rules {
+            return Err(StrategyError::RulesMismatch {
+                expected: self.rules,
+                found: other.rules,
+            });
+        }
+        other.set_piece(self.piece);
+
+        let mut conflicts = Vec::new();
+        for (b, theirs) in other.known_boards {
+            let Some(ours) = self.known_boards.get_mut(&b) else {
+                self.known_boards.insert(b, theirs);
+                continue;
+            };
+
+            if ours.evaluation.conflicts_with(&theirs.evaluation) {
+                conflicts.push(Conflict {
+                    board: b,
+                    kept: ours.evaluation.clone(),
+                    discarded: theirs.evaluation,
+                });
+            } else if ours.evaluation == theirs.evaluation {
+                for c in theirs.move_options {
+                    if !ours.move_options.contains(&c) {
+                        ours.move_options.push(c);
+                    }
+                }
+                ours.depth_used = ours.depth_used.max(theirs.depth_used);
+            } else if !ours.evaluation.is_known()
+                && (theirs.evaluation.is_known() || theirs.depth_used > ours.depth_used)
+            {
+                *ours = theirs;
+            }
+        }
+
+        Ok(conflicts)
+    }
+}
+
+fn standardized_coord(scrambled: &ScrambledBoard, original: Coord) -> Coord {
+    for row in 0..scrambled.rows {
+        for col in 0..scrambled.cols {
+            let c = Coord { row, col };
+            if scrambled.space_at(c).unwrap().to_coord() == original {
+                return c;
+            }
+        }
+    }
+    panic!("coordinate {:?} not on board", original)
+}
@@ -1,45 +1,111 @@
 use crate::board::GridError;
-use crate::players::{AiLazy, AiParallel, AiRandom, AiSerial, Human, Player};
-use crate::space::{Piece, Coord};
-use crate::Board;
+use crate::clock::TimeControl;
+use crate::rules::{Bias, MakerBreaker, OrderChaos, Rules};
+use crate::players::{
+    Action, AiLazy, AiParallel, AiRandom, AiRandomTurn, AiSerial, Human, Player, Request,
+};
+use crate::space::{parse_move, Piece, Coord};
+use crate::strategy::StrategyError;
+use crate::win_condition::Goal;
+use crate::{parse_dimensions, Board};
 use inquire::validator::{ErrorMessage, StringValidator};
 use inquire::{
     validator::{CustomTypeValidator, Validation},
     CustomType, InquireError, Text,
 };
-use inquire::{CustomUserError, Select};
+use inquire::{Confirm, CustomUserError, Select};
 use std::fmt::Display;
+use std::time::{Duration, Instant};
 
-pub fn get_board_size() -> usize {
-    let size = CustomType::<usize>::new("Enter a size for the board:")
-        .with_formatter(&|s| format!("{} by {}", s, s))
+// rows and columns, entered as a single number for a square board or e.g. 3x4
+pub fn get_board_size() -> (usize, usize) {
+    let size = Text::new("Enter a size for the board:")
+        .with_help_message("One number for a square board, or rows by columns such as 3x4")
         .with_validator(BoardSizeValidator)
-        .with_error_message("You must enter a positive whole number")
         .prompt();
 
     match size {
-        Ok(s) => s,
+        Ok(s) => parse_dimensions(&s).expect("Input was not validated properly"),
         Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
         Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
         Err(e) => panic!("{}", e),
     }
 }
 
+// far beyond anything the solvers can handle, but fine for human or random players
+const MAX_BOARD_SIZE: usize = 99;
+
 #[derive(Clone)]
 struct BoardSizeValidator;
-impl CustomTypeValidator<usize> for BoardSizeValidator {
-    fn validate(&self, input: &usize) -> Result<Validation, inquire::CustomUserError> {
-        match input {
-            i if i == &0 => Ok(Validation::Invalid(ErrorMessage::Custom(
-                "Must choose a size greater than 0".to_string(),
+impl StringValidator for BoardSizeValidator {
+    fn validate(&self, input: &str) -> Result<Validation, CustomUserError> {
+        match parse_dimensions(input) {
+            None => Ok(Validation::Invalid(ErrorMessage::Custom(
+                "You must enter a positive whole number, or two separated by x".to_string(),
             ))),
-            i if i > &9 => Ok(Validation::Invalid(ErrorMessage::Custom(format!(
-                "{} by {} board is too large, choose a number less than 10",
-                i, i
-            )))),
-            _ => Ok(Validation::Valid),
+            Some((rows, cols)) if rows > MAX_BOARD_SIZE || cols > MAX_BOARD_SIZE => {
+                Ok(Validation::Invalid(ErrorMessage::Custom(format!(
+                    "{} by {} board is too large, choose numbers no more than {}",
+                    rows, cols, MAX_BOARD_SIZE
+                ))))
+            }
+            Some(_) => Ok(Validation::Valid),
+        }
+    }
+}
+
+// The board to start from, with any spaces blocked and any handicap pieces placed for either player
+pub fn get_start(rows: usize, cols: usize) -> Board {
+    let mut b = Board::new(rows, cols);
+    for piece in [Piece::Blocked, Piece::X, Piece::O] {
+        let message = match piece {
+            Piece::Blocked => "Enter any spaces to block:".to_string(),
+            _ => format!("Enter any spaces for {}'s handicap pieces:", piece),
+        };
+        for c in get_spaces(&message, &b) {
+            b.place(piece, c.row, c.col).unwrap();
         }
     }
+    b
+}
+
+// any number of empty spaces on b, separated by spaces
+fn get_spaces(message: &str, b: &Board) -> Vec<Coord> {
+    let spaces = Text::new(message)
+        .with_help_message("Spaces such as A1 C3, or nothing for none")
+        .with_validator(SpacesValidator { b: b.clone() })
+        .prompt();
+
+    match spaces {
+        Ok(s) => s
+            .split_whitespace()
+            .map(|c| c.parse().expect("Input was not validated properly"))
+            .collect(),
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[derive(Clone)]
+struct SpacesValidator {
+    b: Board,
+}
+impl StringValidator for SpacesValidator {
+    fn validate(&self, input: &str) -> Result<Validation, CustomUserError> {
+        // placed one at a time so that the same space given twice is caught
+        let mut b = self.b.clone();
+        for token in input.split_whitespace() {
+            let c: Coord = match token.parse() {
+                Ok(c) => c,
+                Err(e) => return Ok(Validation::Invalid(ErrorMessage::Custom(format!("{}", e)))),
+            };
+            if let Err(e) = b.place(Piece::Blocked, c.row, c.col) {
+                return Ok(Validation::Invalid(ErrorMessage::Custom(format!("{}: {}", c, e))));
+            }
+        }
+        Ok(Validation::Valid)
+    }
 }
 
 enum PlayerSelection {
@@ -49,6 +115,7 @@ enum PlayerSelection {
     Deterministic,
     Efficient,
     Comprehensive,
+    RandomTurn,
 }
 
 impl Display for PlayerSelection {
@@ -60,16 +127,17 @@ impl Display for PlayerSelection {
             Self::Deterministic => "Deterministic perfect AI",
             Self::Efficient => "Somewhat predictable perfect AI",
             Self::Comprehensive => "Unpredictable perfect AI",
+            Self::RandomTurn => "Perfect random-turn AI",
         };
         write!(f, "{}", str)
     }
 }
 
 impl PlayerSelection {
-    pub fn to_player(&self, piece: Piece, board_size: usize) -> Box<dyn Player> {
+    pub fn to_player(&self, piece: Piece, rows: usize, cols: usize, rules: Rules) -> Box<dyn Player> {
         match self {
             Self::Human => {
-                let new_player = Box::new(Human::new(&get_name(piece), piece));
+                let new_player = Box::new(Human::new(&get_name(piece), piece, rules));
                 new_player
             }
             Self::Random => {
@@ -77,30 +145,38 @@ impl PlayerSelection {
                 new_player
             }
             Self::LimitedDepth => {
-                let mut new_player =
-                    Box::new(AiSerial::new(board_size, piece, get_depth(board_size)));
-                new_player.load_strategy();
+                let depth = get_depth(rows * cols);
+                let mut new_player = Box::new(AiSerial::new(rows, cols, piece, depth, rules));
+                report_unreadable(new_player.load_strategy());
+                new_player.start_checkpointing();
                 new_player
             }
             Self::Deterministic => {
-                let mut new_player = Box::new(AiLazy::new(board_size, piece, true));
-                new_player.load_strategy();
+                let mut new_player = Box::new(AiLazy::new(rows, cols, piece, true, rules));
+                report_unreadable(new_player.load_strategy());
                 new_player
             }
             Self::Efficient => {
-                let mut new_player = Box::new(AiParallel::new(board_size, piece));
-                new_player.load_strategy();
+                let mut new_player = Box::new(AiParallel::new(rows, cols, piece, rules));
+                report_unreadable(new_player.load_strategy());
+                new_player.start_checkpointing();
                 new_player
             }
             Self::Comprehensive => {
-                let mut new_player = Box::new(AiSerial::new(board_size, piece, usize::MAX));
-                new_player.load_strategy();
+                let mut new_player = Box::new(AiSerial::new(rows, cols, piece, usize::MAX, rules));
+                report_unreadable(new_player.load_strategy());
+                new_player.start_checkpointing();
                 new_player
             }
+            Self::RandomTurn => Box::new(AiRandomTurn::new(piece, rules)),
         }
     }
 
-    pub fn variants() -> Vec<Self> {
+    pub fn variants(rules: Rules) -> Vec<Self> {
+        if rules.random_turn {
+            // the other AIs assume the players take turns
+            return vec![Self::Human, Self::Random, Self::RandomTurn];
+        }
         vec![
             Self::Human,
             Self::Random,
@@ -112,23 +188,291 @@ impl PlayerSelection {
     }
 }
 
-pub fn get_player(piece: Piece, board_size: usize) -> Box<dyn Player> {
+// an AI carries on without a saved strategy it can't read, after saying why
+fn report_unreadable(loaded: Result<bool, StrategyError>) {
+    if let Err(e) = loaded {
+        println!("Could not read saved strategy: {}", e);
+    }
+}
+
+enum GoalSelection {
+    Transversal,
+    PartialTransversal,
+    InARow,
+}
+
+impl Display for GoalSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Transversal => "A piece in every row and every column",
+            Self::PartialTransversal => "Pieces in some number of different rows and columns",
+            Self::InARow => "A line of pieces in a row, as in tic-tac-toe",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+enum ModeSelection {
+    BothMaking,
+    MakerFirst,
+    BreakerFirst,
+    OrderFirst,
+    ChaosFirst,
+    RandomTurn,
+}
+
+impl Display for ModeSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::BothMaking => "Both players try to meet the goal",
+            Self::MakerFirst => "Maker-Breaker, with the Maker moving first",
+            Self::BreakerFirst => "Maker-Breaker, with the Breaker moving first",
+            Self::OrderFirst => "Order and Chaos, placing either piece, with Order moving first",
+            Self::ChaosFirst => "Order and Chaos, placing either piece, with Chaos moving first",
+            Self::RandomTurn => "Random-turn, with a coin flip deciding who moves each turn",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+pub fn get_rules(rows: usize, cols: usize) -> Rules {
+    let options = vec![
+        GoalSelection::Transversal,
+        GoalSelection::PartialTransversal,
+        GoalSelection::InARow,
+    ];
+    let goal = match Select::new("Select the goal:", options).prompt() {
+        Ok(GoalSelection::Transversal) => Goal::Transversal,
+        Ok(GoalSelection::PartialTransversal) => Goal::PartialTransversal(get_goal_size(
+            "Enter how many pieces in different rows and columns win:",
+            rows.min(cols),
+        )),
+        Ok(GoalSelection::InARow) => {
+            Goal::InARow(get_goal_size("Enter how many in a row win:", rows.max(cols)))
+        }
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    };
+
+    let options = vec![
+        ModeSelection::BothMaking,
+        ModeSelection::MakerFirst,
+        ModeSelection::BreakerFirst,
+        ModeSelection::OrderFirst,
+        ModeSelection::ChaosFirst,
+        ModeSelection::RandomTurn,
+    ];
+    let mode = Select::new("Select the mode:", options).prompt();
+    let (maker_breaker, order_chaos, random_turn) = match mode {
+        Ok(ModeSelection::BothMaking) => (None, None, false),
+        Ok(ModeSelection::MakerFirst) => (Some(MakerBreaker::MakerFirst), None, false),
+        Ok(ModeSelection::BreakerFirst) => (Some(MakerBreaker::BreakerFirst), None, false),
+        Ok(ModeSelection::OrderFirst) => (None, Some(OrderChaos::OrderFirst), false),
+        Ok(ModeSelection::ChaosFirst) => (None, Some(OrderChaos::ChaosFirst), false),
+        Ok(ModeSelection::RandomTurn) => (None, None, true),
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    };
+
+    // with no order of turns, there are no turns to bias or to swap sides after
+    let (bias, pie) = if random_turn {
+        (Bias::default(), false)
+    } else {
+        (get_bias(), get_pie())
+    };
+
+    let options = vec![
+        Rules {
+            goal,
+            misere: false,
+            maker_breaker,
+            order_chaos,
+            bias,
+            pie,
+            random_turn,
+            ..Rules::default()
+        },
+        Rules {
+            goal,
+            misere: true,
+            maker_breaker,
+            order_chaos,
+            bias,
+            pie,
+            random_turn,
+            ..Rules::default()
+        },
+    ];
+    let rules = Select::new("Select the rules:", options)
+        .with_help_message("Under misere rules, meeting the goal loses")
+        .prompt();
+
+    match rules {
+        Ok(r) => r,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn get_pie() -> bool {
+    match Confirm::new("Use the pie rule?")
+        .with_default(false)
+        .with_help_message("Under the pie rule, the second player may swap sides after the first turn")
+        .prompt()
+    {
+        Ok(r) => r,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => false,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+// how many pieces each player places per turn, entered as e.g. 1:2
+fn get_bias() -> Bias {
+    let bias = Text::new("Enter how many pieces each player places per turn:")
+        .with_default("1:1")
+        .with_help_message("First player's pieces, then second player's, such as 1:2")
+        .with_validator(BiasValidator)
+        .prompt();
+
+    match bias {
+        Ok(b) => b.parse().expect("Input was not validated properly"),
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[derive(Clone)]
+struct BiasValidator;
+impl StringValidator for BiasValidator {
+    fn validate(&self, input: &str) -> Result<Validation, CustomUserError> {
+        match input.parse::<Bias>() {
+            Ok(_) => Ok(Validation::Valid),
+            Err(()) => Ok(Validation::Invalid(ErrorMessage::Custom(
+                "You must enter two positive whole numbers separated by :".to_string(),
+            ))),
+        }
+    }
+}
+
+// the number of pieces a goal needs, which can't be more than max_size
+fn get_goal_size(message: &str, max_size: usize) -> usize {
+    let size = CustomType::<usize>::new(message)
+        .with_formatter(&|l| format!("{} pieces", l))
+        .with_validator(GoalSizeValidator { max_size })
+        .with_error_message("You must enter a positive whole number")
+        .prompt();
+
+    match size {
+        Ok(s) => s,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[derive(Clone)]
+struct GoalSizeValidator {
+    max_size: usize,
+}
+impl CustomTypeValidator<usize> for GoalSizeValidator {
+    fn validate(&self, input: &usize) -> Result<Validation, inquire::CustomUserError> {
+        match input {
+            i if i == &0 => Ok(Validation::Invalid(ErrorMessage::Custom(
+                "Must choose a number greater than 0".to_string(),
+            ))),
+            i if i > &self.max_size => Ok(Validation::Invalid(ErrorMessage::Custom(format!(
+                "Board only has room for {}",
+                self.max_size
+            )))),
+            _ => Ok(Validation::Valid),
+        }
+    }
+}
+
+pub fn get_player(piece: Piece, rows: usize, cols: usize, rules: Rules) -> Box<dyn Player> {
     let message = format!("Select a player type for {}:", piece);
-    let player_choice = Select::new(&message, PlayerSelection::variants()).prompt();
+    let player_choice = Select::new(&message, PlayerSelection::variants(rules)).prompt();
 
     match player_choice {
-        Ok(p) => p.to_player(piece, board_size),
+        Ok(p) => p.to_player(piece, rows, cols, rules),
         Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
         Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
         Err(e) => panic!("{}", e),
     }
 }
 
-fn get_depth(board_size: usize) -> usize {
+enum ClockSelection {
+    Unlimited,
+    SuddenDeath,
+    Increment,
+    PerMove,
+}
+
+impl Display for ClockSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Unlimited => "No clock",
+            Self::SuddenDeath => "Fixed time for the game",
+            Self::Increment => "Time for the game plus time per move",
+            Self::PerMove => "Fixed time per move",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+// None if the player has no clock
+pub fn get_time_control(piece: Piece) -> Option<TimeControl> {
+    let options = vec![
+        ClockSelection::Unlimited,
+        ClockSelection::SuddenDeath,
+        ClockSelection::Increment,
+        ClockSelection::PerMove,
+    ];
+    let message = format!("Select a clock for {}:", piece);
+    let clock_choice = match Select::new(&message, options).prompt() {
+        Ok(c) => c,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    };
+
+    match clock_choice {
+        ClockSelection::Unlimited => None,
+        ClockSelection::SuddenDeath => {
+            Some(TimeControl::SuddenDeath(get_seconds("Seconds for the game:")))
+        }
+        ClockSelection::Increment => Some(TimeControl::Increment {
+            base: get_seconds("Seconds for the game:"),
+            increment: get_seconds("Seconds added per move:"),
+        }),
+        ClockSelection::PerMove => Some(TimeControl::PerMove(get_seconds("Seconds per move:"))),
+    }
+}
+
+fn get_seconds(message: &str) -> Duration {
+    let seconds = CustomType::<u64>::new(message)
+        .with_formatter(&|s| format!("{}s", s))
+        .with_error_message("You must enter a whole number")
+        .prompt();
+
+    match seconds {
+        Ok(s) => Duration::from_secs(s),
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => panic!("User interrupted with esc"),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn get_depth(spaces: usize) -> usize {
     let depth = CustomType::<usize>::new("Enter a depth for AI analysis:")
         .with_formatter(&|d| format!("{} moves", d))
         .with_help_message("How many moves ahead the AI should consider")
-        .with_validator(DepthValidator { board_size })
+        .with_validator(DepthValidator { spaces })
         .with_error_message("You must enter a positive whole number")
         .prompt();
 
@@ -142,11 +486,11 @@ fn get_depth(board_size: usize) -> usize {
 
 #[derive(Clone)]
 struct DepthValidator {
-    board_size: usize,
+    spaces: usize,
 }
 impl CustomTypeValidator<usize> for DepthValidator {
     fn validate(&self, input: &usize) -> Result<Validation, inquire::CustomUserError> {
-        let max_moves = self.board_size * self.board_size;
+        let max_moves = self.spaces;
         match input {
             i if i == &0 => Ok(Validation::Invalid(ErrorMessage::Custom(
                 "Must choose a number greater than 0".to_string(),
@@ -178,120 +522,186 @@ fn get_name(piece: Piece) -> String {
 struct MoveSyntaxValidator;
 impl StringValidator for MoveSyntaxValidator {
     fn validate(&self, input: &str) -> Result<Validation, CustomUserError> {
-        let standard_err = Ok(Validation::Invalid(ErrorMessage::Custom(
-            "Invalid space. Enter in the form \"A1\"".to_string(),
-        )));
-        let mut chars = input.chars();
-
-        // first character
-        match chars.next() {
-            None => {
-                return standard_err;
-            },
-            Some(c) => {
-                if !c.is_ascii_alphabetic() {
-                    return standard_err;
-                }
-            }
+        if request_from_str(input).is_some() {
+            return Ok(Validation::Valid);
         }
 
-        // second character
-        match chars.next() {
-            None => {
-                return standard_err;
-            },
-            Some(c) => {
-                if !c.is_numeric() {
-                    return standard_err;
-                }
-            }
-        }
-        
-        if let Some(_) = chars.next() {
-            return standard_err;
+        match parse_move(input) {
+            Ok(_) => Ok(Validation::Valid),
+            Err(e) => Ok(Validation::Invalid(ErrorMessage::Custom(e.to_string()))),
         }
-
-        Ok(Validation::Valid)
     }
 }
 
 #[derive(Clone)]
-struct MovePlacementValidator {b: Board}
+struct MovePlacementValidator {b: Board, allowed: Vec<Piece>}
 impl StringValidator for MovePlacementValidator {
     fn validate(&self, input: &str) -> Result<Validation, CustomUserError> {
-        let input = input.to_uppercase();
-
-        let mut chars = input.chars();
-
-        // first character
-        let col_char = chars.next().expect("Input was not validated properly");
-        let col = col_char as u8 - b'A';
-
-        // second character
-        let row_char = chars.next().expect("Input was not validated properly");
-        let row = row_char as u8 - b'1';
-        
+        if request_from_str(input).is_some() {
+            return Ok(Validation::Valid);
+        }
+        let (c, piece) = parse_move(input).expect("Input was not validated properly");
+        if let Some(piece) = piece.filter(|p| !self.allowed.contains(p)) {
+            return Ok(Validation::Invalid(ErrorMessage::Custom(
+                format!("You can't place {} in this game", piece)
+            )));
+        }
 
-        
-        match self.b.clone().place(Piece::X, row as usize, col as usize) {
+        match self.b.clone().place(Piece::X, c.row, c.col) {
             Err(GridError::RowIndexOutOfBounds { .. }) => {
                 Ok(Validation::Invalid(ErrorMessage::Custom(
-                    format!("Invalid space, row {} out of bounds", row_char)
+                    format!("Invalid space, row {} out of bounds", c.row + 1)
                 )))
             }
             Err(GridError::ColIndexOutOfBounds { .. }) => {
                 Ok(Validation::Invalid(ErrorMessage::Custom(
-                    format!("Invalid space, column {} out of bounds", col_char)
+                    format!("Invalid space, column {} out of bounds", Coord::col_label(c.col))
                 )))
             }
             Err(GridError::SpaceOccupied { .. }) => {
                 Ok(Validation::Invalid(ErrorMessage::Custom(
-                    format!("Space {}{} is already occupied", col_char, row_char)
+                    format!("Space {} is already occupied", c)
                 )))
             }
+            Err(GridError::SpaceBlocked { .. }) => {
+                Ok(Validation::Invalid(ErrorMessage::Custom(
+                    format!("Space {} is blocked", c)
+                )))
+            }
+            Err(e) => Ok(Validation::Invalid(ErrorMessage::Custom(e.to_string()))),
             Ok(_) => Ok(Validation::Valid),
         }
     }
 }
-impl MovePlacementValidator {
-    // assumes string is already in the correct format
-    fn coord_from_str(move_str: &str) -> Coord{
-        let move_str = move_str.to_uppercase();
 
-        let row: u8;
-        let col: u8;
+pub enum ReplayCommand {
+    Forward,
+    Back,
+    Start,
+    End,
+    Quit,
+}
 
-        let mut chars = move_str.chars();
-        if let Some(col_char) = chars.next() {
-            col = col_char as u8 - b'A';
-        }
-        else {
-            panic!("Input was not validated properly")
-        }
+impl Display for ReplayCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Self::Forward => "Next move",
+            Self::Back => "Previous move",
+            Self::Start => "Go to start",
+            Self::End => "Go to end",
+            Self::Quit => "Quit",
+        };
+        write!(f, "{}", str)
+    }
+}
 
-        // second character
-        if let Some(row_char) = chars.next() {
-            row = row_char as u8 - b'1';
-        }
-        else {
-            panic!("Input was not validated properly")
-        }
+pub fn get_replay_command(can_go_back: bool, can_go_forward: bool) -> ReplayCommand {
+    let mut options = Vec::new();
+    if can_go_forward {
+        options.push(ReplayCommand::Forward);
+    }
+    if can_go_back {
+        options.push(ReplayCommand::Back);
+        options.push(ReplayCommand::Start);
+    }
+    if can_go_forward {
+        options.push(ReplayCommand::End);
+    }
+    options.push(ReplayCommand::Quit);
 
-        Coord {row: row as usize, col: col as usize}
+    match Select::new("Replay:", options).prompt() {
+        Ok(c) => c,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => ReplayCommand::Quit,
+        Err(e) => panic!("{}", e),
     }
 }
 
-pub fn get_move(name: &str, b: &Board) -> Coord {
+pub fn get_draw_response(name: &str) -> bool {
+    let response = Confirm::new(&format!("{}, your opponent offers a draw. Accept?", name))
+        .with_default(false)
+        .prompt();
+
+    match response {
+        Ok(r) => r,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => false,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+pub fn get_swap_response(name: &str) -> bool {
+    let response = Confirm::new(&format!("{}, swap sides and take your opponent's move?", name))
+        .with_default(false)
+        .prompt();
+
+    match response {
+        Ok(r) => r,
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => false,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+// None if the user chose not to save
+pub fn get_save_path() -> Option<String> {
+    let path = Text::new("Save game to:")
+        .with_help_message("Leave empty to skip saving")
+        .prompt();
+
+    match path {
+        Ok(p) if p.trim().is_empty() => None,
+        Ok(p) => Some(p.trim().to_string()),
+        Err(InquireError::OperationInterrupted) => panic!("User interrupted with ^C"),
+        Err(InquireError::OperationCanceled) => None,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+// any action other than placing a piece
+fn request_from_str(input: &str) -> Option<Action> {
+    match input.trim().to_lowercase().as_str() {
+        "undo" => Some(Action::Request(Request::Undo)),
+        "redo" => Some(Action::Request(Request::Redo)),
+        "save" => Some(Action::Request(Request::Save)),
+        "resign" => Some(Action::Resign),
+        "draw" => Some(Action::OfferDraw),
+        _ => None,
+    }
+}
+
+// The move of the player with piece, who may give another piece to place if the rules allow it
+pub fn get_move(name: &str, piece: Piece, b: &Board, deadline: Option<Instant>, rules: Rules) -> Action {
+    let allowed = rules.pieces_for(piece);
     let validators: Vec<Box<dyn StringValidator>> = vec![
         Box::new(MoveSyntaxValidator),
-        Box::new(MovePlacementValidator {b: b.clone()})
+        Box::new(MovePlacementValidator {b: b.clone(), allowed: allowed.clone()})
     ];
-    let move_result = Text::new(&format!("{}, enter your move: ", name))
+    let mut help = "\"undo\" takes back your last move, \"redo\" replays it, \"save\" saves the game so far, \"draw\" offers a draw, \"resign\" concedes".to_string();
+    if allowed.len() > 1 {
+        help = format!("A space such as B2 places your own piece, B2=X or B2=O either one. {}", help);
+    }
+    let prompt = match deadline {
+        Some(d) => format!(
+            "{}, enter your move ({}s left): ",
+            name,
+            d.saturating_duration_since(Instant::now()).as_secs()
+        ),
+        None => format!("{}, enter your move: ", name),
+    };
+    let move_result = Text::new(&prompt)
+    .with_help_message(&help)
     .with_validators(&validators)
     .prompt();
 
     match move_result {
-        Ok(move_str) => MovePlacementValidator::coord_from_str(&move_str),
+        Ok(move_str) => match request_from_str(&move_str) {
+            Some(action) => action,
+            None => {
+                let (c, placed) = parse_move(&move_str).expect("Input was not validated properly");
+                Action::Place(c, placed.unwrap_or(piece))
+            }
+        },
         _ => panic!("Encountered an error")
     }
 
@@ -0,0 +1,256 @@
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use crate::space::{Coord, Piece};
+use crate::{Board, ScrambledBoard};
+
+// What a player has to build on the board to win. The solvers only look at one board from each
+// class of equivalent boards, so standardize must only ever use symmetries that keep every win a win.
+// Implementations from outside the crate are played through Goal::Custom.
+pub trait WinCondition {
+    // the spaces of piece's that make up a win, if piece has one
+    fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>>;
+
+    fn has_win(&self, b: &Board, piece: Piece) -> bool {
+        self.winning_spaces(b, piece).is_some()
+    }
+
+    // rearranges b into the one board that stands for every board equivalent to it
+    fn standardize(&self, b: &mut ScrambledBoard);
+
+    // a random board equivalent to b
+    fn scramble(&self, b: &Board, rng: &mut dyn RngCore) -> Board;
+}
+
+// TAG's rule: one of the player's pieces in every row and every column, with no two in the same
+// row or column. On a rectangular board that means every row or every column, whichever there
+// are fewer of. Any permutation of rows, any permutation of columns and transposing a square board
+// all leave a transversal a transversal.
+pub struct Transversal;
+
+impl WinCondition for Transversal {
+    fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>> {
+        b.transversal(piece)
+    }
+
+    fn standardize(&self, b: &mut ScrambledBoard) {
+        b.standardize();
+    }
+
+    fn scramble(&self, b: &Board, rng: &mut dyn RngCore) -> Board {
+        permute_rows_and_cols(b, rng)
+    }
+}
+
+// pieces of the player's in `pieces` different rows and `pieces` different columns, which is a
+// transversal when pieces is the shorter side of the board. Has the same symmetries as a transversal.
+pub struct PartialTransversal {
+    pub pieces: usize,
+}
+
+impl WinCondition for PartialTransversal {
+    fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>> {
+        if self.pieces == 0 {
+            return None;
+        }
+        b.partial_transversal(piece, self.pieces)
+    }
+
+    fn standardize(&self, b: &mut ScrambledBoard) {
+        b.standardize();
+    }
+
+    fn scramble(&self, b: &Board, rng: &mut dyn RngCore) -> Board {
+        permute_rows_and_cols(b, rng)
+    }
+}
+
+// applies a random permutation of rows and of columns, and possibly a transpose if b is square
+fn permute_rows_and_cols(b: &Board, rng: &mut dyn RngCore) -> Board {
+    let mut rows: Vec<usize> = (0..b.rows).collect();
+    let mut cols: Vec<usize> = (0..b.cols).collect();
+    rows.shuffle(rng);
+    cols.shuffle(rng);
+    let transpose = b.is_square() && rng.gen_bool(0.5);
+
+    let mut scrambled = Board::new(b.rows, b.cols);
+    for (row, &from_row) in rows.iter().enumerate() {
+        for (col, &from_col) in cols.iter().enumerate() {
+            let piece = b.piece_at(from_row, from_col).unwrap();
+            if transpose {
+                scrambled.place(piece, col, row).unwrap();
+            } else {
+                scrambled.place(piece, row, col).unwrap();
+            }
+        }
+    }
+    scrambled
+}
+
+// Tic-tac-toe's rule: length of the player's pieces in a line across a row, down a column or
+// along a diagonal. Only rotations and reflections keep lines intact, and only reflections keep a
+// rectangular board's shape.
+pub struct InARow {
+    pub length: usize,
+}
+
+impl WinCondition for InARow {
+    fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>> {
+        if self.length == 0 || self.length > b.rows.max(b.cols) {
+            return None;
+        }
+
+        // right, down, down and right, down and left
+        let directions = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        for row in 0..b.rows {
+            for col in 0..b.cols {
+                for (d_row, d_col) in directions {
+                    let line: Option<Vec<Coord>> = (0..self.length as isize)
+                        .map(|i| {
+                            let row = row.checked_add_signed(i * d_row)?;
+                            let col = col.checked_add_signed(i * d_col)?;
+                            match b.piece_at(row, col) {
+                                Ok(p) if p == piece => Some(Coord { row, col }),
+                                _ => None,
+                            }
+                        })
+                        .collect();
+                    if line.is_some() {
+                        return line;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn standardize(&self, b: &mut ScrambledBoard) {
+        b.standardize_dihedral();
+    }
+
+    fn scramble(&self, b: &Board, rng: &mut dyn RngCore) -> Board {
+        let mut scrambled = ScrambledBoard::from(b.clone());
+        if b.is_square() && rng.gen_bool(0.5) {
+            scrambled.transpose();
+        }
+        if rng.gen_bool(0.5) {
+            scrambled.flip_rows();
+        }
+        if rng.gen_bool(0.5) {
+            scrambled.flip_cols();
+        }
+        Board::from(&scrambled)
+    }
+}
+
+// A win condition from outside the crate, declared as a static and named so that Rules can still
+// be copied, compared and written to strategy file names. Goals with the same name are taken to be
+// the same goal, so the name should be a single word unique to the condition. Records and strategy
+// file names with a custom goal can't be read back, since only the crate's own goals are known by
+// name.
+pub struct CustomGoal {
+    pub name: &'static str,
+    pub condition: &'static (dyn WinCondition + Sync),
+}
+
+impl PartialEq for CustomGoal {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for CustomGoal {}
+
+impl Hash for CustomGoal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+impl Debug for CustomGoal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CustomGoal").field(&self.name).finish()
+    }
+}
+
+// Every win condition a game can be played with, as a plain value so that Rules can be copied,
+// compared and written to records and strategy file names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Goal {
+    #[default]
+    Transversal,
+    InARow(usize),
+    PartialTransversal(usize),
+    Custom(&'static CustomGoal),
+}
+
+impl Goal {
+    fn with_condition<T>(&self, f: impl FnOnce(&dyn WinCondition) -> T) -> T {
+        match *self {
+            Goal::Transversal => f(&Transversal),
+            Goal::InARow(length) => f(&InARow { length }),
+            Goal::PartialTransversal(pieces) => f(&PartialTransversal { pieces }),
+            Goal::Custom(custom) => f(custom.condition),
+        }
+    }
+
+    // added to strategy file names so strategies for different goals are kept apart
+    pub fn file_suffix(&self) -> String {
+        match self {
+            Goal::Transversal => String::new(),
+            Goal::InARow(length) => format!("-row{}", length),
+            Goal::PartialTransversal(pieces) => format!("-partial{}", pieces),
+            Goal::Custom(custom) => format!("-{}", custom.name),
+        }
+    }
+}
+
+impl WinCondition for Goal {
+    fn winning_spaces(&self, b: &Board, piece: Piece) -> Option<Vec<Coord>> {
+        self.with_condition(|c| c.winning_spaces(b, piece))
+    }
+
+    fn standardize(&self, b: &mut ScrambledBoard) {
+        self.with_condition(|c| c.standardize(b))
+    }
+
+    fn scramble(&self, b: &Board, rng: &mut dyn RngCore) -> Board {
+        self.with_condition(|c| c.scramble(b, rng))
+    }
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Goal::Transversal => write!(f, "transversal"),
+            Goal::InARow(length) => write!(f, "{}-in-a-row", length),
+            Goal::PartialTransversal(pieces) => write!(f, "{}-partial-transversal", pieces),
+            Goal::Custom(custom) => write!(f, "{}", custom.name),
+        }
+    }
+}
+
+// "transversal", or e.g. "3-in-a-row" or "3-partial-transversal"
+impl FromStr for Goal {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "transversal" {
+            return Ok(Goal::Transversal);
+        }
+        if let Some(Ok(length)) = s.strip_suffix("-in-a-row").map(|n| n.parse()) {
+            if length > 0 {
+                return Ok(Goal::InARow(length));
+            }
+        }
+        if let Some(Ok(pieces)) = s.strip_suffix("-partial-transversal").map(|n| n.parse()) {
+            if pieces > 0 {
+                return Ok(Goal::PartialTransversal(pieces));
+            }
+        }
+        Err(())
+    }
+}